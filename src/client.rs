@@ -0,0 +1,44 @@
+// Helpers for bots and scripts operating the contract through the generated client.
+// The clients passed in must already be authorized as the right account: the attendee
+// for registration and the admin for distribution.
+
+use crate::{BatchResult, DistributionContractClient, MAX_BATCH};
+use crate::token::{Identifier, Signature};
+
+pub use crate::token::Client as TokenClient;
+
+// Approve exactly what the registration costs right now and register. `token` must be
+// authorized as the account paying, `contract` as the one registering.
+pub fn register_and_approve(
+    contract: &DistributionContractClient,
+    token: &TokenClient,
+    attendee: &Identifier,
+    tier: Option<u32>
+) -> (u32, i128) {
+    let amount = contract.quote(attendee, &tier);
+    token.incr_allow(&Signature::Invoker, &0, &Identifier::Contract(contract.contract_id.clone()), &amount);
+    match tier {
+        Some(tier_id) => contract.dep_tier(attendee, &tier_id),
+        None => contract.deposit(attendee, &None)
+    }
+}
+
+// Run `withdraw` batch after batch until every attendee who can be paid was paid.
+// Attendees held back (frozen, missing feedback) are left in `remaining`.
+pub fn drive_distribution_to_completion(contract: &DistributionContractClient) -> BatchResult {
+    let mut total = BatchResult { paid: 0, skipped: 0, total: 0, remaining: 0 };
+    let mut low = 0;
+    loop {
+        let batch = contract.withdraw(&(low + MAX_BATCH), &low);
+        total.paid += batch.paid;
+        total.skipped += batch.skipped;
+        total.total += batch.total;
+        total.remaining = batch.remaining;
+
+        // Past the last withdrawal index
+        if batch.remaining == 0 || batch.paid + batch.skipped == 0 {
+            return total;
+        }
+        low += MAX_BATCH;
+    }
+}
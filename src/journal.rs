@@ -0,0 +1,76 @@
+// Every storage write of the contract goes through here. With the `journal` feature, writes
+// are also mirrored to an in-memory journal of (key, old value, new value), so tests can
+// assert on exact state transitions rather than on balances only.
+
+use soroban_sdk::{Env, IntoVal, RawVal};
+
+#[cfg(feature = "journal")]
+extern crate std;
+
+#[cfg(feature = "journal")]
+use soroban_sdk::{xdr::ScVal, TryIntoVal};
+#[cfg(feature = "journal")]
+use std::{cell::RefCell, vec::Vec};
+
+#[cfg(feature = "journal")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JournalEntry {
+    // For `DataKey` entries, a vector starting with the variant name
+    pub key: ScVal,
+    // `None` when the entry didn't exist before, or was removed
+    pub old: Option<ScVal>,
+    pub new: Option<ScVal>
+}
+
+#[cfg(feature = "journal")]
+std::thread_local! {
+    static JOURNAL: RefCell<Vec<JournalEntry>> = RefCell::new(Vec::new());
+}
+
+#[cfg(feature = "journal")]
+fn to_scval(e: &Env, val: RawVal) -> ScVal {
+    val.try_into_val(e).unwrap()
+}
+
+#[cfg(feature = "journal")]
+fn record(e: &Env, key: RawVal, new: Option<RawVal>) {
+    let mut old = None;
+    if e.storage().has(key) {
+        let val: RawVal = e.storage().get_unchecked(key).unwrap();
+        old = Some(to_scval(e, val));
+    }
+    let entry = JournalEntry { key: to_scval(e, key), old, new: new.map(|val| to_scval(e, val)) };
+    JOURNAL.with(|journal| journal.borrow_mut().push(entry));
+}
+
+pub fn set<K: IntoVal<Env, RawVal>, V: IntoVal<Env, RawVal>>(e: &Env, key: K, val: V) {
+    let key: RawVal = key.into_val(e);
+    let val: RawVal = val.into_val(e);
+    #[cfg(feature = "journal")]
+    record(e, key, Some(val));
+    e.storage().set(key, val);
+}
+
+pub fn remove<K: IntoVal<Env, RawVal>>(e: &Env, key: K) {
+    let key: RawVal = key.into_val(e);
+    #[cfg(feature = "journal")]
+    record(e, key, None);
+    e.storage().remove(key);
+}
+
+// Writes since the last `clear`, oldest first
+#[cfg(feature = "journal")]
+pub fn entries() -> Vec<JournalEntry> {
+    JOURNAL.with(|journal| journal.borrow().clone())
+}
+
+#[cfg(feature = "journal")]
+pub fn clear() {
+    JOURNAL.with(|journal| journal.borrow_mut().clear());
+}
+
+// The journal form of `key`, to compare with `JournalEntry::key`
+#[cfg(feature = "journal")]
+pub fn key_of<K: IntoVal<Env, RawVal>>(e: &Env, key: K) -> ScVal {
+    to_scval(e, key.into_val(e))
+}
@@ -0,0 +1,59 @@
+// Every token movement in or out of the contract, with the contract's running balance,
+// so the books can be audited without replaying chain history
+
+use crate::journal;
+use crate::token::Identifier;
+use crate::DataKey;
+use soroban_sdk::{contracttype, Env, Symbol, Vec};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct LedgerEntry {
+    // What the movement was for, e.g. "deposit", "payout", "refund", "sweep"
+    pub kind: Symbol,
+    // Counterparty the tokens came from or went to
+    pub account: Identifier,
+    // Positive when received by the contract, negative when sent
+    pub amount: i128,
+    // Contract balance after the movement
+    pub balance: i128,
+    pub timestamp: u64
+}
+
+pub fn count(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::LedgerN) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::LedgerN).unwrap()
+}
+
+pub fn record(e: &Env, kind: Symbol, account: Identifier, amount: i128) {
+    let seq = count(e);
+    let mut balance = amount;
+    if seq > 0 {
+        let last: LedgerEntry = e.storage().get_unchecked(DataKey::Ledger(seq - 1)).unwrap();
+        balance += last.balance;
+    }
+
+    let entry = LedgerEntry {
+        kind,
+        account,
+        amount,
+        balance,
+        timestamp: e.ledger().timestamp()
+    };
+    journal::set(e, DataKey::Ledger(seq), entry);
+    journal::set(e, DataKey::LedgerN, seq + 1);
+}
+
+pub fn entries(e: &Env, high: u32, low: u32) -> Vec<LedgerEntry> {
+    let count = count(e);
+    let mut entries = Vec::new(e);
+    for seq in low..high {
+        if seq >= count {
+            break;
+        }
+        entries.push_back(e.storage().get_unchecked(DataKey::Ledger(seq)).unwrap());
+    }
+    entries
+}
@@ -1,205 +1,5310 @@
 #![no_std]
 
-use soroban_sdk::{contractimpl, contracttype, BytesN, Env};
+use soroban_sdk::{contracterror, contractimpl, contracttype, symbol, vec, Bytes, BytesN, Env, IntoVal, RawVal, Symbol, Vec};
 
 mod token {
     soroban_sdk::contractimport!(file = "soroban_token_spec.wasm");
 }
 
+mod oracle {
+    use soroban_sdk::{contractclient, Env};
+
+    #[contractclient(name = "Client")]
+    pub trait Oracle {
+        // Deposit token units per one USD
+        fn price(env: Env) -> i128;
+    }
+}
+
+mod yield_pool {
+    use crate::token::Identifier;
+    use soroban_sdk::{contractclient, Env};
+
+    #[contractclient(name = "Client")]
+    pub trait YieldPool {
+        // Pull `amount` from `from`, which has approved the pool as a spender
+        fn deposit(env: Env, from: Identifier, amount: i128);
+        // Send everything supplied by the invoker plus the accrued yield to `to`, returning the amount
+        fn redeem(env: Env, to: Identifier) -> i128;
+    }
+}
+
+mod swap {
+    use crate::token::Identifier;
+    use soroban_sdk::{contractclient, BytesN, Env};
+
+    #[contractclient(name = "Client")]
+    pub trait Swap {
+        // Pull `amount` of `sell` from `from`, which has approved the swap as a spender, and send
+        // the `buy` tokens bought to `to`. Fails below `min_out`, otherwise returns the amount bought.
+        fn swap(env: Env, from: Identifier, to: Identifier, sell: BytesN<32>, buy: BytesN<32>, amount: i128, min_out: i128) -> i128;
+    }
+}
+
+mod attestation {
+    use crate::token::Identifier;
+    use soroban_sdk::{contractclient, Env};
+
+    #[contractclient(name = "Client")]
+    pub trait Attestation {
+        // Whether `id` passed the attestor's checks (account age, proof of personhood, ...)
+        fn eligible(env: Env, id: Identifier) -> bool;
+    }
+}
+
+mod parent_event {
+    use crate::token::Identifier;
+    use soroban_sdk::{contractclient, Env};
+
+    #[contractclient(name = "Client")]
+    pub trait ParentEvent {
+        // Implemented by this contract, so any deployment of it can act as a parent
+        fn is_reg(env: Env, attendee: Identifier) -> bool;
+    }
+}
+
 use token::{Identifier, Signature};
 
-#[derive(Clone)]
-#[contracttype]
-pub struct Attendee {
-    pub fee: i128,
-    pub attended: bool,
-    pub refunded: bool
+pub mod journal;
+mod ledger;
+pub use ledger::LedgerEntry;
+mod merkle;
+
+// Lifecycle events for notification services. Topics are versioned and never change
+// meaning; a new payload layout gets a new version instead of altering the existing one.
+pub const EVT_V1: Symbol = symbol!("v1");
+// `DepositEvent`, on every registration
+pub const EVT_V1_DEPOSIT: (Symbol, Symbol) = (EVT_V1, symbol!("deposit"));
+// `AttendEvent`
+pub const EVT_V1_ATTEND: (Symbol, Symbol) = (EVT_V1, symbol!("attend"));
+// `FinalizeEvent`, once when the distribution starts
+pub const EVT_V1_FINALIZE: (Symbol, Symbol) = (EVT_V1, symbol!("finalize"));
+// `TransferEvent`, for every payout (or vested claim) sent
+pub const EVT_V1_PAYOUT: (Symbol, Symbol) = (EVT_V1, symbol!("payout"));
+// `TransferEvent`, when a registration is cancelled and its fee returned
+pub const EVT_V1_REFUND: (Symbol, Symbol) = (EVT_V1, symbol!("refund"));
+// `TransferEvent` with the beneficiary as recipient
+pub const EVT_V1_SWEEP: (Symbol, Symbol) = (EVT_V1, symbol!("sweep"));
+// Number of attendees, once the last registrant has checked in
+pub const EVT_V1_ALL_IN: (Symbol, Symbol) = (EVT_V1, symbol!("all_in"));
+// `DonateEvent`, when an attendee gives their payout to a charity
+pub const EVT_V1_DONATE: (Symbol, Symbol) = (EVT_V1, symbol!("donate"));
+
+pub const EVT_V2: Symbol = symbol!("v2");
+// `PayoutEvent`, for every payout (or vested claim) settled, including parts sent to a payer or charity
+pub const EVT_V2_PAYOUT: (Symbol, Symbol) = (EVT_V2, symbol!("payout"));
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct DepositEvent {
+    pub attendee: Identifier,
+    pub reg_index: u32,
+    // Charged now, including any surcharge
+    pub amount: i128
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct AttendEvent {
+    pub attendee: Identifier,
+    // `NO_WITHDRAWAL_INDEX` for comped attendees
+    pub index: u32
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct FinalizeEvent {
+    pub mode: DistributionMode,
+    pub attended: u32,
+    pub unclaimed: i128
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct TransferEvent {
+    pub to: Identifier,
+    pub amount: i128
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct PayoutEvent {
+    pub to: Identifier,
+    // Return of the fee paid
+    pub principal: i128,
+    // Share of the pool, matches and sponsor money
+    pub bonus: i128
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct DonateEvent {
+    pub attendee: Identifier,
+    pub charity: Identifier,
+    pub amount: i128
+}
+
+// Fixed-point precision of USD prices
+const ORACLE_DECIMALS: i128 = 10_000_000;
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Attendee {
+    pub fee: i128,
+    // Amount still owed when registered with the installment plan
+    pub due: i128,
+    // Packed status bits, see the `FLAG_` constants
+    pub flags: u32,
+    // Hash of the artifact proving attendance (photo, signature, ticket scan)
+    pub evidence: Option<BytesN<32>>,
+    // Pricing tier registered with, or `None` for the base price
+    pub tier: Option<u32>,
+    // Order of registration, never reused
+    pub reg_index: u32,
+    // Part of a vested payout claimed so far
+    pub claimed: i128,
+    // Hash of the off-chain survey receipt
+    pub feedback: Option<BytesN<32>>,
+    // Account that paid on the attendee's behalf (e.g. a team captain or an employer)
+    pub payer: Option<Identifier>,
+    // Part of the fee covered by `payer` in basis points. Refunds and payouts are split back the same way.
+    pub payer_bps: u32,
+    // Hash of the attendee's contact details, to later prove where notifications were sent
+    pub contact: Option<BytesN<32>>,
+    // Check-in and check-out times, 0 until recorded
+    pub arrived_at: u64,
+    pub left_at: u64,
+    // Payout received so far, split into the fee returned and the bonus on top of it
+    pub princ_paid: i128,
+    pub bonus_paid: i128
+}
+
+// What is kept of a record erased through `erase_me`, without the identifier
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Tombstone {
+    pub fee: i128,
+    pub flags: u32,
+    pub paid: i128
+}
+
+// User of a shared account, such as an exchange customer, registered through `dep_sub`
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct SubAccount {
+    pub owner: Identifier,
+    pub sub_id: u64
+}
+
+// Why a registration or check-in would be refused, as reported by `can_dep` and
+// `can_attend`. The entrypoints themselves panic with the matching message, see `fail`.
+#[contracterror]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum Error {
+    AdminDeposit = 1,
+    Finalized = 2,
+    AlreadyRegistered = 3,
+    RegistrationClosed = 4,
+    MinimumNotMet = 5,
+    RegistrationCap = 6,
+    EventFull = 7,
+    NotAllowlisted = 8,
+    Blocked = 9,
+    ContractAttendee = 10,
+    NotAttested = 11,
+    NoTier = 12,
+    TierSoldOut = 13,
+    AllowanceTooLow = 14,
+    AdminAttend = 15,
+    NotStarted = 16,
+    CheckInClosed = 17,
+    NotRegistered = 18,
+    AlreadyAttended = 19,
+    DepositIncomplete = 20,
+    SeatLimit = 21,
+    EventNotFinalized = 22,
+    Comped = 23
+}
+
+// Extra charged on registrations from `starts_at` on, in basis points of the price
+#[derive(Clone)]
+#[contracttype]
+pub struct Surcharge {
+    pub starts_at: u64,
+    pub bps: u32,
+    // Send the surcharge to the beneficiary instead of adding it to the refundable deposit
+    pub to_benef: bool
+}
+
+// Outcome of one payout batch
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct BatchResult {
+    pub paid: u32,
+    // Attendees in the range that were already paid or aren't payable yet
+    pub skipped: u32,
+    pub total: i128,
+    // Attendees still waiting for their payout after this batch
+    pub remaining: u32
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Team {
+    pub captain: Identifier,
+    pub members: Vec<Identifier>
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Tier {
+    pub price: i128,
+    pub capacity: u32,
+    pub sold: u32
+}
+
+// What happens to the stroops left over after splitting the unclaimed pool equally
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum RemainderPolicy {
+    // Left in the contract
+    Retain,
+    // First `remainder` attendees by withdrawal index receive one extra stroop
+    FirstPaid,
+    // Sent to the beneficiary via `sweep_rem`
+    Sweep
+}
+
+// Order of withdrawal indexes, fixed at finalization
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum PayoutOrder {
+    // Order attendance was recorded in, i.e. door scan order
+    Attendance,
+    // Order of registration
+    Register,
+    // Ascending SHA-256 of the serialized identifier
+    IdHash
+}
+
+// How the whole pot is divided between attendees at finalization
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum DistributionMode {
+    // Everyone gets back what they paid plus an equal share of the forfeited deposits
+    InKind,
+    // The pot is split proportionally to the fee each attendee paid
+    ProRata,
+    // Everyone gets back what they paid and the forfeited deposits fund raffle prizes
+    Raffle,
+    // The event was cancelled: every registrant, present or not, gets back what they paid
+    Refund,
+    // Everyone gets back what they paid and the forfeited deposits are split by time present
+    ByTime
+}
+
+// Sensitive operations that need approvals once approvers are configured
+#[derive(Clone)]
+#[contracttype]
+pub enum AdminAction {
+    Sweep,
+    SetBenef(Identifier),
+    SetRemain(RemainderPolicy),
+    // Lowest and highest price `initialize` and `set_tier` accept
+    SetBounds(i128, i128),
+    // Pay a frozen attendee's reserved payout to the beneficiary, as `release`
+    Release(Identifier),
+    // Cancel the event and refund everyone, as `cancel`
+    Cancel
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Proposal {
+    pub action: AdminAction,
+    pub approvals: Vec<Identifier>,
+    // Timestamp the timelock expires at
+    pub ready_at: u64,
+    pub executed: bool
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct AuditEntry {
+    pub action: Symbol,
+    pub actor: Identifier,
+    // Attendee or account the action was applied to, if any
+    pub target: Option<Identifier>,
+    pub timestamp: u64
+}
+
+// Everything `initialize` sets up. Optional fields left as `None` keep their defaults
+// and can still be configured later with the matching setter.
+#[derive(Clone)]
+#[contracttype]
+pub struct InitConfig {
+    pub admin: Identifier,
+    pub price: i128,
+    pub token: BytesN<32>,
+    // Resolves disputes and cancels the event instead of the admin
+    pub arbiter: Option<Identifier>,
+    pub benef: Option<Identifier>,
+    // Defaults to `RemainderPolicy::Retain`
+    pub remainder: Option<RemainderPolicy>,
+    // Registrations pay half upfront until this timestamp, see `set_inst`
+    pub inst_due: Option<u64>,
+    // Percentage of registrants who must attend, see `set_quorum`
+    pub quorum: Option<u32>,
+    // Defaults to `PayoutOrder::Attendance`
+    pub order: Option<PayoutOrder>,
+    // `(min, max)` price, checked against `price` too. Changed later only through `AdminAction::SetBounds`.
+    pub bounds: Option<(i128, i128)>,
+    // Take prices and caps passed by the admin, `price` included, in whole tokens rather than
+    // in the token's smallest unit
+    pub whole: bool
+}
+
+impl InitConfig {
+    pub fn new(admin: Identifier, price: i128, token: BytesN<32>) -> Self {
+        InitConfig {
+            admin,
+            price,
+            token,
+            arbiter: None,
+            benef: None,
+            remainder: None,
+            inst_due: None,
+            quorum: None,
+            order: None,
+            bounds: None,
+            whole: false
+        }
+    }
+}
+
+// Timestamps the phases follow without the admin having to act, see `set_sched`
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Schedule {
+    pub reg_end: u64,
+    pub event_at: u64,
+    pub claim_open: u64,
+    pub claim_end: u64
+}
+
+// Where the event is in its lifecycle, derived from storage
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Phase {
+    // Deposits and attendance are open
+    Register,
+    // Past the scheduled registration close, only check-ins are taken
+    CheckIn,
+    // Finalized, some attendees still waiting for their payout
+    Paying,
+    // Every attendee was paid
+    Settled,
+    // Past the scheduled claim close with payouts left, which `sweep_rem` can take
+    Closed,
+    // Cancelled, registrants are refunded through `refund`
+    Cancelled
+}
+
+// Everything an organizer checks day to day, in one call
+#[derive(Clone)]
+#[contracttype]
+pub struct StatusReport {
+    pub phase: Phase,
+    pub mode: Option<DistributionMode>,
+    pub inst_due: Option<u64>,
+    pub final_at: Option<u64>,
+    pub registered: u32,
+    pub attended: u32,
+    // Attendees paid in full
+    pub paid: u32,
+    pub disputes: u32,
+    // Forfeited deposits to be shared
+    pub pool: i128,
+    pub paid_out: i128,
+    // Token balance the contract accounts for
+    pub held: i128
+}
+
+// Page of `export`. Attendees who left are skipped, so a page can hold fewer records than requested.
+#[derive(Clone)]
+#[contracttype]
+pub struct StateChunk {
+    pub stats: Stats,
+    pub token: BytesN<32>,
+    pub mode: Option<DistributionMode>,
+    pub attendees: Vec<(Identifier, Attendee)>,
+    // Registration index to continue from; the export is complete once it reaches `total`
+    pub next: u32,
+    pub total: u32
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Stats {
+    pub price: i128,
+    pub registered: u32,
+    pub attended: u32,
+    pub unclaimed: i128,
+    pub remainder: RemainderPolicy,
+    pub sponsored: i128,
+    // Included in `registered` and `attended`
+    pub comped: u32,
+    // Settled records deleted by `purge` or `erase_me`, still included in the counts above
+    pub purged: u32,
+    // Payouts given to charities through `donate`
+    pub donated: i128
+}
+
+// Bits of `Attendee::flags`. New flags must take unused bits so stored records stay valid.
+pub const FLAG_ATTENDED: u32 = 1 << 0;
+pub const FLAG_REFUNDED: u32 = 1 << 1;
+// Admitted without a deposit; excluded from the payout
+pub const FLAG_COMPED: u32 = 1 << 2;
+// Claims to have attended without being marked, waiting for `resolve`
+pub const FLAG_DISPUTED: u32 = 1 << 3;
+// Payouts and cancellation held while the admin investigates
+pub const FLAG_FROZEN: u32 = 1 << 4;
+// The token refused the last batch payout; skipped by `withdraw` until retried with `payout_one`
+pub const FLAG_PAY_FAILED: u32 = 1 << 5;
+// Checked in during the grace period and refunded the fee alone at once
+pub const FLAG_LATE: u32 = 1 << 6;
+
+// Returned by `attend` for comped attendees, who get no withdrawal index
+pub const NO_WITHDRAWAL_INDEX: u32 = u32::MAX;
+
+impl Attendee {
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+
+    pub fn set_flag(&mut self, flag: u32) {
+        self.flags |= flag;
+    }
+
+    pub fn clear_flag(&mut self, flag: u32) {
+        self.flags &= !flag;
+    }
+
+    pub fn attended(&self) -> bool {
+        self.has_flag(FLAG_ATTENDED)
+    }
+
+    pub fn refunded(&self) -> bool {
+        self.has_flag(FLAG_REFUNDED)
+    }
+
+    pub fn comped(&self) -> bool {
+        self.has_flag(FLAG_COMPED)
+    }
+
+    pub fn disputed(&self) -> bool {
+        self.has_flag(FLAG_DISPUTED)
+    }
+
+    pub fn frozen(&self) -> bool {
+        self.has_flag(FLAG_FROZEN)
+    }
+
+    pub fn payout_failed(&self) -> bool {
+        self.has_flag(FLAG_PAY_FAILED)
+    }
+
+    pub fn late(&self) -> bool {
+        self.has_flag(FLAG_LATE)
+    }
+}
+
+// Attendee record as stored before keys were namespaced under `DataKey`
+#[derive(Clone)]
+#[contracttype]
+pub struct LegacyAttendee {
+    pub fee: i128,
+    pub attended: bool,
+    pub refunded: bool
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum DataKey {
+    Admin,
+    Attendee,
+    Withdraws,
+    Unclaimed,
+    Price,
+    Token,
+    InstDue,
+    Registered,
+    Remainder,
+    Benef,
+    Swept,
+    AttRec(Identifier),
+    // Number of raw `u32` withdrawal keys in the legacy layout
+    Count,
+    StorageVer,
+    AllowMode,
+    Allowed(Identifier),
+    Blocked(Identifier),
+    Oracle,
+    UsdPrice,
+    // Set at finalization; deposits and attendance are frozen from then on
+    Mode,
+    AttFees,
+    PaidOut,
+    Refunds,
+    Tier(u32),
+    // Ids of all configured tiers
+    Tiers,
+    // Token balance the contract has accounted for
+    Held,
+    CheckptN,
+    NextReg,
+    NoContract,
+    // Ring buffer of the last `AUDIT_LOG_SIZE` admin actions, keyed by sequence number modulo the size
+    AuditLog(u32),
+    AuditCount,
+    Approvers,
+    Threshold,
+    Proposal(u32),
+    ProposalN,
+    // Ids of proposals that haven't been executed yet
+    Pending,
+    Timelock,
+    // Payouts unlock linearly over this many seconds after finalization
+    Vesting,
+    FinalAt,
+    // Total contributed by sponsors, kept apart from forfeited deposits
+    SponsPool,
+    Sponsor(Identifier),
+    MatchBps,
+    MatchCap,
+    // Match rate in effect after finalization
+    MatchRate,
+    Prizes,
+    // Withdrawal indexes of raffle winners, drawn at finalization
+    Winners,
+    Team(Symbol),
+    Surcharge,
+    Comped,
+    CompAtt,
+    // Most an attendee can receive from forfeited deposits
+    BonusCap,
+    FeedReq,
+    // Identifier registered with a given registration index
+    RegId(u32),
+    // Attendee bound to an external ticket, and the reverse
+    Ticket(BytesN<32>),
+    TicketOf(Identifier),
+    // Number of disputes waiting for resolution
+    Disputes,
+    Arbiter,
+    // Percentage of registrants who must attend for the distribution to go ahead
+    Quorum,
+    YieldPool,
+    // Deposits currently supplied to the yield pool
+    Supplied,
+    // Co-organizers sharing the beneficiary proceeds, with weights in basis points
+    Splits,
+    // Proceeds waiting for `pay_fees`
+    FeesOwed,
+    // Progress of `close` through the per-attendee, audit log and proposal entries
+    CloseCur,
+    Closed,
+    Order,
+    Purged,
+    // Token movements recorded by the `ledger` module
+    Ledger(u32),
+    LedgerN,
+    // Current venue check-in nonce and when it expires
+    Nonce,
+    // Seats at the venue and the percentage of them that may register
+    Capacity,
+    Overbook,
+    // Sorted, disjoint `(low, high)` ranges of withdrawal indexes already paid
+    Processed,
+    // Low-privilege key allowed to run payout batches
+    AutoKey,
+    // Charities attendees may donate their payout to
+    Charity(Identifier),
+    Donated,
+    // Swap contract payouts can be converted through
+    Swap,
+    // Commitment to the final attendee state, built by `commit`
+    MerkleCur,
+    MerkleN,
+    Frontier,
+    MerkleRoot,
+    // Free event where the sponsor pool is split between attendees
+    Rsvp,
+    Bounds,
+    // Token minted to registrants as a receipt, see `set_rcpt`
+    ReceiptTok,
+    Schedule,
+    // Token decimals cached at initialization
+    Decimals,
+    WholeUnits,
+    // Seconds after the scheduled check-in close that late arrivals are still taken
+    Grace,
+    Late,
+    // Sum of the time checked-out attendees were present, see `check_out`
+    TotalTime,
+    // Contract vouching for registrants, see `set_attest`
+    Attestor,
+    // Parent event contract and the bundle discount in basis points, see `set_parent`
+    Parent,
+    // Left at the registration index of an erased record
+    Tombstone(u32),
+    // `(until, price)` steps of the price schedule, see `set_steps`
+    PriceSteps,
+    // Registrations needed by a deadline for the event to go ahead, see `set_minreg`
+    MinRegs,
+    // Referral bonus per referred attendee who showed up, as configured and as paid after finalization
+    RefBonus,
+    RefShare,
+    // Referred attendees who showed up, in total and per referrer
+    Referred,
+    Referrals(Identifier),
+    // Who referred the attendee
+    Referrer(Identifier),
+    // Most registrations one identifier may pay for, and how many each has, see `set_seats`
+    SeatLimit,
+    Seats(Identifier),
+    // Registrants currently frozen, and the payouts held back for them at sweep time
+    FrozenIds,
+    Reserved(Identifier),
+    // Account and sub-id behind an identifier registered through `dep_sub`
+    SubAcct(Identifier),
+    // Tokens reported through `notify_pay` and not spent on `prepaid` yet, per
+    // sender and in total
+    Credit(Identifier),
+    Credited,
+    // Keys written per identifier or name, in order, so `close` can find them again
+    Tracked(u32),
+    TrackedN
+}
+
+const AUDIT_LOG_SIZE: u32 = 200;
+
+const STORAGE_VERSION: u32 = 2;
+
+// Most attendees a batch entrypoint processes in one call. Sized so a full batch of
+// the costliest entrypoint (`withdraw`) stays within the budget checked by the bench suite.
+pub const MAX_BATCH: u32 = 10;
+
+// Absolute limit on registrations, whatever the tier capacities, so per-event storage and
+// the loops over attendees stay within what the network can execute
+pub const MAX_REGISTRATIONS: u32 = 10_000;
+
+// Seconds a check-in nonce stays valid, so a code seen at the venue is useless soon after
+pub const NONCE_TTL: u64 = 300;
+
+// Bumped whenever the public interface changes
+const CONTRACT_VERSION: u32 = 3;
+
+pub struct DistributionContract;
+
+// Free events (price 0) are fine, negative prices are not
+fn check_price(price: i128) {
+    if price < 0 {
+        panic!("negative price");
+    }
+}
+
+// Contracts initialized before decimals were cached ask the token
+fn get_decimals(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::Decimals) {
+        return token::Client::new(e, &get_token(e)).decimals();
+    }
+    e.storage().get_unchecked(DataKey::Decimals).unwrap()
+}
+
+// Token units of an amount the admin passed, which is in whole tokens if configured so
+fn admin_amount(e: &Env, amount: i128) -> i128 {
+    if !e.storage().has(DataKey::WholeUnits) {
+        return amount;
+    }
+    match 10i128.checked_pow(get_decimals(e)).and_then(|unit| amount.checked_mul(unit)) {
+        Some(units) => units,
+        None => panic!("amount overflow")
+    }
+}
+
+// Guards against mistyped prices, such as a wrong number of decimals
+fn check_price_bounds(e: &Env, price: i128) {
+    if let Some((min, max)) = get_price_bounds(e) {
+        if price < min || price > max {
+            panic!("price out of bounds");
+        }
+    }
+}
+
+fn get_price_bounds(e: &Env) -> Option<(i128, i128)> {
+    if !e.storage().has(DataKey::Bounds) {
+        return None;
+    }
+    Some(e.storage().get_unchecked(DataKey::Bounds).unwrap())
+}
+
+fn write_price_bounds(e: &Env, min: i128, max: i128) {
+    check_price(min);
+    if min > max {
+        panic!("Invalid bounds");
+    }
+    journal::set(e, DataKey::Bounds, (min, max));
+}
+
+// `a * b / c` that panics instead of wrapping on huge token amounts
+fn mul_div(a: i128, b: i128, c: i128) -> i128 {
+    match a.checked_mul(b) {
+        Some(product) => product / c,
+        None => panic!("amount overflow")
+    }
+}
+
+fn get_price(e: &Env) -> i128 {
+    e.storage().get_unchecked(DataKey::Price).unwrap()
+}
+
+// Price in token units at this moment. With an oracle configured, the USD
+// price is converted at the current rate.
+fn current_price(e: &Env) -> i128 {
+    if !e.storage().has(DataKey::Oracle) {
+        return match current_step(e) {
+            Some(step) => get_price_steps(e).get_unchecked(step).unwrap().1,
+            None => get_price(e)
+        };
+    }
+
+    let oracle_id: BytesN<32> = e.storage().get_unchecked(DataKey::Oracle).unwrap();
+    let usd_price: i128 = e.storage().get_unchecked(DataKey::UsdPrice).unwrap();
+    let rate = oracle::Client::new(e, &oracle_id).price();
+    if rate <= 0 {
+        panic!("invalid oracle price");
+    }
+    mul_div(usd_price, rate, ORACLE_DECIMALS)
+}
+
+fn get_price_steps(e: &Env) -> Vec<(u64, i128)> {
+    if !e.storage().has(DataKey::PriceSteps) {
+        return Vec::new(e);
+    }
+    e.storage().get_unchecked(DataKey::PriceSteps).unwrap()
+}
+
+// Position of the step in effect now, `None` once the last one ended
+fn current_step(e: &Env) -> Option<u32> {
+    let now = e.ledger().timestamp();
+    let steps = get_price_steps(e);
+    for step in 0..steps.len() {
+        let (until, _) = steps.get_unchecked(step).unwrap();
+        if now < until {
+            return Some(step);
+        }
+    }
+    None
+}
+
+fn get_token(e: &Env) -> BytesN<32> {
+    e.storage().get_unchecked(DataKey::Token).unwrap()
+}
+
+// Attended identifiers, in the order attendance was recorded. Position in the list is the withdrawal ID.
+fn get_withdrawals(e: &Env) -> Vec<Identifier> {
+    e.storage().get_unchecked(DataKey::Withdraws).unwrap()
+}
+
+fn get_count(e: &Env) -> u32 {
+    get_withdrawals(e).len()
+}
+
+fn get_unclaimed(e: &Env) -> i128 {
+    e.storage().get_unchecked(DataKey::Unclaimed).unwrap()
+}
+
+// Split payments are offered until the installment deadline, which is also
+// the last moment to pay the remainder
+fn installments_open(e: &Env) -> bool {
+    if !e.storage().has(DataKey::InstDue) {
+        return false;
+    }
+    let deadline: u64 = e.storage().get_unchecked(DataKey::InstDue).unwrap();
+    e.ledger().timestamp() <= deadline
+}
+
+// Each function call is limited in resources, so we limit how many attendees can be processed in one call
+fn check_range(high: u32, low: u32) {
+    if high < low || high - low > MAX_BATCH
+    {
+        panic!("Invalid range")
+    }
+}
+
+// Equal share of the forfeited deposits each attendee receives on top of their fee, zero if nobody attended
+fn get_share(e: &Env) -> i128 {
+    let withdrawal_count = get_count(e);
+    let unclaimed = get_unclaimed(e);
+    unclaimed.checked_div(withdrawal_count as i128).unwrap_or(0)
+}
+
+fn get_remainder(e: &Env) -> i128 {
+    let withdrawal_count = get_count(e);
+    let unclaimed = get_unclaimed(e);
+    unclaimed.checked_rem(withdrawal_count as i128).unwrap_or(0)
+}
+
+fn get_remainder_policy(e: &Env) -> RemainderPolicy {
+    e.storage().get_unchecked(DataKey::Remainder).unwrap()
+}
+
+fn is_finalized(e: &Env) -> bool {
+    e.storage().has(DataKey::Mode)
+}
+
+fn check_not_finalized(e: &Env) {
+    if is_finalized(e) {
+        panic!("distribution already started");
+    }
+}
+
+// Mode the payouts are computed with; events that aren't finalized yet are previewed in kind
+fn get_mode(e: &Env) -> DistributionMode {
+    if !is_finalized(e) {
+        return DistributionMode::InKind;
+    }
+    e.storage().get_unchecked(DataKey::Mode).unwrap()
+}
+
+fn get_attended_fees(e: &Env) -> i128 {
+    e.storage().get_unchecked(DataKey::AttFees).unwrap()
+}
+
+fn write_finalized(e: &Env, mut mode: DistributionMode) {
+    redeem_supplied(e);
+    if !quorum_met(e) || min_registrations_missed(e) {
+        mode = DistributionMode::Refund;
+    }
+    if mode == DistributionMode::Refund {
+        journal::set(e, DataKey::Mode, mode);
+        journal::set(e, DataKey::FinalAt, e.ledger().timestamp());
+        e.events().publish(EVT_V1_FINALIZE, FinalizeEvent { mode, attended: get_count(e), unclaimed: get_unclaimed(e) });
+        return;
+    }
+
+    // Nothing to distribute until someone shows up, short of cancelling
+    if get_attended(e) == 0 {
+        panic!("no attendees");
+    }
+    // Round-robin remainder relies on every attendee getting the same share
+    if mode != DistributionMode::InKind && get_remainder_policy(e) == RemainderPolicy::FirstPaid {
+        panic!("remainder policy not supported for mode");
+    }
+    // Disputed deposits stay in escrow until resolved, so the pot isn't known yet
+    if get_open_disputes(e) > 0 {
+        panic!("disputes pending");
+    }
+    journal::set(e, DataKey::Mode, mode);
+    reorder_withdrawals(e);
+
+    if mode == DistributionMode::Raffle {
+        let prizes = get_raffle_prizes(e);
+        if prizes == 0 {
+            panic!("raffle prizes not set");
+        }
+        let winners = draw_winners(e, &raffle_seed(e), prizes, get_count(e));
+        journal::set(e, DataKey::Winners, winners);
+    }
+    // Referral bonuses come out of the pool, lowered evenly if it can't cover them all
+    let referred = get_referred(e);
+    if e.storage().has(DataKey::RefBonus) && referred > 0 {
+        let mut share: i128 = e.storage().get_unchecked(DataKey::RefBonus).unwrap();
+        let unclaimed = get_unclaimed(e);
+        if share * referred as i128 > unclaimed {
+            share = unclaimed / referred as i128;
+        }
+        journal::set(e, DataKey::RefShare, share);
+        journal::set(e, DataKey::Unclaimed, unclaimed - share * referred as i128);
+    }
+    journal::set(e, DataKey::FinalAt, e.ledger().timestamp());
+    e.events().publish(EVT_V1_FINALIZE, FinalizeEvent { mode, attended: get_count(e), unclaimed: get_unclaimed(e) });
+
+    // Lower the match rate if sponsors didn't contribute enough to match everyone in full
+    if e.storage().has(DataKey::MatchBps) {
+        let mut rate: u32 = e.storage().get_unchecked(DataKey::MatchBps).unwrap();
+        let attended_fees = get_attended_fees(e);
+        let pool = get_sponsor_pool(e);
+        if mul_div(attended_fees, rate as i128, 10_000) > pool {
+            rate = mul_div(pool, 10_000, attended_fees) as u32;
+        }
+        journal::set(e, DataKey::MatchRate, rate);
+    }
+}
+
+fn get_payout_order(e: &Env) -> PayoutOrder {
+    if !e.storage().has(DataKey::Order) {
+        return PayoutOrder::Attendance;
+    }
+    e.storage().get_unchecked(DataKey::Order).unwrap()
+}
+
+// Rewrite the withdrawal list in the configured payout order. Must run before anything
+// that depends on withdrawal indexes (remainder stroops, raffle draws) is computed.
+fn reorder_withdrawals(e: &Env) {
+    match get_payout_order(e) {
+        PayoutOrder::Attendance => {}
+        PayoutOrder::Register => {
+            let registrations: u32 = e.storage().get_unchecked(DataKey::NextReg).unwrap();
+            let mut ordered = Vec::new(e);
+            for index in 0..registrations {
+                if !e.storage().has(DataKey::RegId(index)) {
+                    continue;
+                }
+                let id: Identifier = e.storage().get_unchecked(DataKey::RegId(index)).unwrap();
+                if !has_attendee(e, &id) {
+                    continue;
+                }
+                let att = read_attendee(e, &id);
+                if att.reg_index == index && att.attended() && !att.comped() {
+                    ordered.push_back(id);
+                }
+            }
+            journal::set(e, DataKey::Withdraws, ordered);
+        }
+        PayoutOrder::IdHash => {
+            // Binary insertion keyed by the hashes kept alongside
+            let mut ordered: Vec<Identifier> = Vec::new(e);
+            let mut hashes: Vec<BytesN<32>> = Vec::new(e);
+            for id in get_withdrawals(e).iter() {
+                let id = id.unwrap();
+                let hash = e.crypto().sha256(&e.serialize_to_bytes(id.clone()));
+                let mut low = 0;
+                let mut high = hashes.len();
+                while low < high {
+                    let mid = (low + high) / 2;
+                    if hashes.get_unchecked(mid).unwrap() < hash {
+                        low = mid + 1;
+                    } else {
+                        high = mid;
+                    }
+                }
+                hashes.insert(low, hash);
+                ordered.insert(low, id);
+            }
+            journal::set(e, DataKey::Withdraws, ordered);
+        }
+    }
+}
+
+fn get_supplied(e: &Env) -> i128 {
+    if !e.storage().has(DataKey::Supplied) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Supplied).unwrap()
+}
+
+// Take the deposits back from the yield pool, adding the yield to the forfeited deposits
+fn redeem_supplied(e: &Env) {
+    let supplied = get_supplied(e);
+    if supplied == 0 {
+        return;
+    }
+    let pool: BytesN<32> = e.storage().get_unchecked(DataKey::YieldPool).unwrap();
+    let redeemed = yield_pool::Client::new(e, &pool).redeem(&get_contract_id(e));
+    journal::set(e, DataKey::Supplied, 0 as i128);
+    journal::set(e, DataKey::Held, get_held(e) + redeemed);
+    ledger::record(e, symbol!("redeem"), Identifier::Contract(pool), redeemed);
+    journal::set(e, DataKey::Unclaimed, get_unclaimed(e) + redeemed - supplied);
+}
+
+fn quorum_met(e: &Env) -> bool {
+    if !e.storage().has(DataKey::Quorum) {
+        return true;
+    }
+    let quorum: u32 = e.storage().get_unchecked(DataKey::Quorum).unwrap();
+    let registered: u32 = e.storage().get_unchecked(DataKey::Registered).unwrap();
+    let attended = get_attended(e);
+    attended as u64 * 100 >= registered as u64 * quorum as u64
+}
+
+// Past the decision deadline with too few registrations, the event is off
+fn min_registrations_missed(e: &Env) -> bool {
+    if !e.storage().has(DataKey::MinRegs) {
+        return false;
+    }
+    let (minimum, deadline): (u32, u64) = e.storage().get_unchecked(DataKey::MinRegs).unwrap();
+    let registered: u32 = e.storage().get_unchecked(DataKey::Registered).unwrap();
+    e.ledger().timestamp() >= deadline && registered < minimum
+}
+
+fn get_referred(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::Referred) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Referred).unwrap()
+}
+
+fn get_referrals(e: &Env, referrer: &Identifier) -> u32 {
+    if !e.storage().has(DataKey::Referrals(referrer.clone())) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Referrals(referrer.clone())).unwrap()
+}
+
+fn get_vesting(e: &Env) -> u64 {
+    if !e.storage().has(DataKey::Vesting) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Vesting).unwrap()
+}
+
+// Vested payouts are claimed by attendees instead of being pushed in batches
+fn check_not_vested(e: &Env) {
+    if get_vesting(e) > 0 {
+        panic!("payouts are vested");
+    }
+}
+
+// Part of a `total` payout vested since finalization, all of it without vesting
+fn vested_amount(e: &Env, total: i128) -> i128 {
+    let duration = get_vesting(e);
+    if duration == 0 {
+        return total;
+    }
+    let finalized_at: u64 = e.storage().get_unchecked(DataKey::FinalAt).unwrap();
+    let mut elapsed = e.ledger().timestamp() - finalized_at;
+    if elapsed > duration {
+        elapsed = duration;
+    }
+    total * elapsed as i128 / duration as i128
+}
+
+// Payouts finalize the distribution in kind unless `finalize` was called before
+fn ensure_finalized(e: &Env) {
+    if !is_finalized(e) {
+        write_finalized(e, DistributionMode::InKind);
+    }
+}
+
+// Transfer the payout of the attendee at withdrawal index `id` and mark them refunded
+fn pay_out(e: &Env, token: &BytesN<32>, id: u32, att: &Identifier, att_struct: Attendee) -> i128 {
+    let distribution_amount = payout_amount(e, id, &att_struct);
+    for (to, part) in unpaid_parts(att, &att_struct, distribution_amount) {
+        transfer_from_contract_to_account(e, token, &to, &part, symbol!("payout"));
+    }
+    e.events().publish(EVT_V1_PAYOUT, TransferEvent { to: att.clone(), amount: distribution_amount });
+    settle(e, id, att, att_struct, distribution_amount);
+    distribution_amount
+}
+
+// Mark the attendee at withdrawal index `id` as paid `distribution_amount`
+fn settle(e: &Env, id: u32, att: &Identifier, mut att_struct: Attendee, distribution_amount: i128) {
+    record_payout(e, att, &mut att_struct, distribution_amount);
+    if e.storage().has(DataKey::Reserved(att.clone())) {
+        journal::remove(e, DataKey::Reserved(att.clone()));
+    }
+    att_struct.set_flag(FLAG_REFUNDED);
+    att_struct.clear_flag(FLAG_PAY_FAILED);
+    write_attendee(e, att, att_struct);
+
+    let paid_out: i128 = e.storage().get_unchecked(DataKey::PaidOut).unwrap();
+    journal::set(e, DataKey::PaidOut, paid_out + distribution_amount);
+    let refunds: u32 = e.storage().get_unchecked(DataKey::Refunds).unwrap();
+    journal::set(e, DataKey::Refunds, refunds + 1);
+    // Refunds of late check-ins and cancellations have no withdrawal slot to track
+    if id != NO_WITHDRAWAL_INDEX {
+        mark_processed(e, id);
+    }
+}
+
+// Settle an attendee paid `amount` out of a vesting payout, which only completes the payout
+// once `fully_vested`. Until then the vested part paid so far is kept in `claimed`.
+fn settle_vested(e: &Env, id: u32, att: &Identifier, mut att_struct: Attendee, amount: i128, fully_vested: bool) {
+    if fully_vested {
+        settle(e, id, att, att_struct, amount);
+        return;
+    }
+    att_struct.claimed += amount;
+    record_payout(e, att, &mut att_struct, amount);
+    write_attendee(e, att, att_struct);
+
+    let paid_out: i128 = e.storage().get_unchecked(DataKey::PaidOut).unwrap();
+    journal::set(e, DataKey::PaidOut, paid_out + amount);
+}
+
+// Book `amount` paid to `att` as principal until the fee is returned, the rest as bonus
+fn record_payout(e: &Env, att: &Identifier, att_struct: &mut Attendee, amount: i128) {
+    let mut principal = att_struct.fee - att_struct.princ_paid;
+    if principal > amount {
+        principal = amount;
+    }
+    if principal < 0 {
+        principal = 0;
+    }
+    let bonus = amount - principal;
+    att_struct.princ_paid += principal;
+    att_struct.bonus_paid += bonus;
+    e.events().publish(EVT_V2_PAYOUT, PayoutEvent { to: att.clone(), principal, bonus });
+}
+
+fn get_processed(e: &Env) -> Vec<(u32, u32)> {
+    if !e.storage().has(DataKey::Processed) {
+        return Vec::new(e);
+    }
+    e.storage().get_unchecked(DataKey::Processed).unwrap()
+}
+
+// Merge `index` into the processed ranges, joining its neighbours when it closes a gap
+fn mark_processed(e: &Env, index: u32) {
+    let mut ranges = get_processed(e);
+    let mut pos = 0;
+    while pos < ranges.len() {
+        let (low, high) = ranges.get_unchecked(pos).unwrap();
+        if index < low {
+            break;
+        }
+        if index < high {
+            return;
+        }
+        pos += 1;
+    }
+
+    let mut low = index;
+    let mut high = index + 1;
+    if pos > 0 {
+        let (prev_low, prev_high) = ranges.get_unchecked(pos - 1).unwrap();
+        if prev_high == index {
+            low = prev_low;
+            pos -= 1;
+            ranges.remove(pos);
+        }
+    }
+    if pos < ranges.len() {
+        let (next_low, next_high) = ranges.get_unchecked(pos).unwrap();
+        if next_low == high {
+            high = next_high;
+            ranges.remove(pos);
+        }
+    }
+    ranges.insert(pos, (low, high));
+    journal::set(e, DataKey::Processed, ranges);
+}
+
+fn get_raffle_prizes(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::Prizes) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Prizes).unwrap()
+}
+
+// The SDK has no PRNG, so the raffle is seeded from ledger state at finalization
+fn raffle_seed(e: &Env) -> BytesN<32> {
+    let mut data = Bytes::new(e);
+    data.extend_from_array(&e.ledger().timestamp().to_be_bytes());
+    data.extend_from_array(&e.ledger().sequence().to_be_bytes());
+    data.extend_from_array(&get_count(e).to_be_bytes());
+    e.crypto().sha256(&data)
+}
+
+// Pick up to `prizes` distinct withdrawal indexes out of `count`, rehashing the seed for every draw
+fn draw_winners(e: &Env, seed: &BytesN<32>, prizes: u32, count: u32) -> Vec<u32> {
+    let mut winners = Vec::new(e);
+    let mut target = prizes;
+    if target > count {
+        target = count;
+    }
+
+    let mut hash = seed.clone();
+    while winners.len() < target {
+        hash = e.crypto().sha256(&hash.clone().into());
+        let mut value: u32 = 0;
+        for i in 0..4 {
+            value = (value << 8) | hash.get_unchecked(i) as u32;
+        }
+
+        let index = value % count;
+        if !winners.contains(index) {
+            winners.push_back(index);
+        }
+    }
+    winners
+}
+
+// With feedback required, payouts unlock only after the attendee submitted it
+fn feedback_pending(e: &Env, att: &Attendee) -> bool {
+    e.storage().has(DataKey::FeedReq) && att.feedback.is_none()
+}
+
+fn get_sponsor_pool(e: &Env) -> i128 {
+    if !e.storage().has(DataKey::SponsPool) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::SponsPool).unwrap()
+}
+
+// Sponsor match on top of the attendee's payout, at the rate fixed at finalization
+fn match_amount(e: &Env, att: &Attendee) -> i128 {
+    if !e.storage().has(DataKey::MatchRate) {
+        return 0;
+    }
+    let rate: u32 = e.storage().get_unchecked(DataKey::MatchRate).unwrap();
+    let cap: i128 = e.storage().get_unchecked(DataKey::MatchCap).unwrap();
+    let amount = mul_div(att.fee, rate as i128, 10_000);
+    if amount > cap {
+        return cap;
+    }
+    amount
+}
+
+// Amount paid out to the attendee stored at withdrawal index `id`
+fn payout_amount(e: &Env, id: u32, att: &Attendee) -> i128 {
+    let mut amount = base_payout(e, id, att);
+
+    // Anything above the cap is left for the beneficiary
+    if e.storage().has(DataKey::BonusCap) {
+        let cap: i128 = e.storage().get_unchecked(DataKey::BonusCap).unwrap();
+        if amount - att.fee > cap {
+            amount = att.fee + cap;
+        }
+    }
+    amount + match_amount(e, att) + sponsor_share(e)
+}
+
+// Equal part of the sponsor pool for every paid attendee of an RSVP event
+fn sponsor_share(e: &Env) -> i128 {
+    if !e.storage().has(DataKey::Rsvp) || get_mode(e) == DistributionMode::Refund {
+        return 0;
+    }
+    let count = get_count(e);
+    if count == 0 {
+        return 0;
+    }
+    get_sponsor_pool(e) / count as i128
+}
+
+// Payout funded by attendee deposits alone
+fn base_payout(e: &Env, id: u32, att: &Attendee) -> i128 {
+    if get_mode(e) == DistributionMode::Refund {
+        return att.fee;
+    }
+
+    if get_mode(e) == DistributionMode::Raffle {
+        let winners: Vec<u32> = e.storage().get_unchecked(DataKey::Winners).unwrap();
+        if winners.contains(id) {
+            return att.fee + get_unclaimed(e) / winners.len() as i128;
+        }
+        return att.fee;
+    }
+
+    // Attendees who never checked out were present for no recorded time
+    let total_time = get_total_time(e);
+    if get_mode(e) == DistributionMode::ByTime && total_time > 0 {
+        let mut present = 0;
+        if att.left_at != 0 {
+            present = (att.left_at - att.arrived_at) as i128;
+        }
+        return att.fee + mul_div(get_unclaimed(e), present, total_time as i128);
+    }
+
+    // With only free tickets attending there is nothing to weigh by, so split evenly
+    let attended_fees = get_attended_fees(e);
+    if get_mode(e) == DistributionMode::ProRata && attended_fees > 0 {
+        return mul_div(att.fee, attended_fees + get_unclaimed(e), attended_fees);
+    }
+
+    let mut amount = att.fee + get_share(e);
+    if get_remainder_policy(e) == RemainderPolicy::FirstPaid && (id as i128) < get_remainder(e) {
+        amount += 1;
+    }
+    amount
+}
+
+impl From<LegacyAttendee> for Attendee {
+    fn from(legacy: LegacyAttendee) -> Self {
+        let mut att = Attendee {
+            fee: legacy.fee,
+            due: 0,
+            flags: 0,
+            evidence: None,
+            tier: None,
+            reg_index: 0,
+            claimed: 0,
+            feedback: None,
+            payer: None,
+            payer_bps: 0,
+            contact: None,
+            arrived_at: 0,
+            left_at: 0,
+            princ_paid: 0,
+            bonus_paid: 0
+        };
+        if legacy.attended {
+            att.set_flag(FLAG_ATTENDED);
+        }
+        if legacy.refunded {
+            att.set_flag(FLAG_REFUNDED);
+        }
+        att
+    }
+}
+
+// Split `amount` of `id`'s fees between the payer and the attendee
+fn split_amount(id: &Identifier, att: &Attendee, amount: i128) -> [(Identifier, i128); 2] {
+    match &att.payer {
+        Some(payer) => {
+            let payer_amount = mul_div(amount, att.payer_bps as i128, 10_000);
+            [(payer.clone(), payer_amount), (id.clone(), amount - payer_amount)]
+        }
+        None => [(id.clone(), 0), (id.clone(), amount)]
+    }
+}
+
+// Split a payout of `amount` like `split_amount`, less what a failed batch payout already sent.
+// Batches pay the payer's part first and keep the sent amount in `claimed`.
+fn unpaid_parts(id: &Identifier, att: &Attendee, amount: i128) -> [(Identifier, i128); 2] {
+    let mut sent = att.claimed;
+    let mut parts = split_amount(id, att, amount);
+    for part in parts.iter_mut() {
+        let paid = if sent < part.1 { sent } else { part.1 };
+        part.1 -= paid;
+        sent -= paid;
+    }
+    parts
+}
+
+// Charge `amount` of `id`'s fees to whoever pays for them
+fn collect(e: &Env, token: &BytesN<32>, id: &Identifier, att: &Attendee, amount: i128, kind: Symbol) {
+    for (from, part) in split_amount(id, att, amount) {
+        transfer_from_account_to_contract(e, token, &from, &part, kind.clone());
+    }
+}
+
+// Send refunds and payouts of `id` back to whoever paid for them
+fn pay_back(e: &Env, token: &BytesN<32>, id: &Identifier, att: &Attendee, amount: i128, kind: Symbol) {
+    for (to, part) in split_amount(id, att, amount) {
+        transfer_from_contract_to_account(e, token, &to, &part, kind.clone());
+    }
+}
+
+fn has_attendee(e: &Env, id: &Identifier) -> bool {
+    e.storage().has(DataKey::AttRec(id.clone()))
+}
+
+fn read_attendee(e: &Env, id: &Identifier) -> Attendee {
+    e.storage().get_unchecked(DataKey::AttRec(id.clone())).unwrap()
+}
+
+fn write_attendee(e: &Env, id: &Identifier, att: Attendee) {
+    journal::set(e, DataKey::AttRec(id.clone()), att);
+}
+
+// Move a record stored directly under the identifier into `DataKey::AttRec`.
+// Returns false if there is no legacy record for `id`.
+fn migrate_attendee(e: &Env, id: &Identifier) -> bool {
+    if !e.storage().has(id.clone()) {
+        return false;
+    }
+
+    let legacy: LegacyAttendee = e.storage().get_unchecked(id.clone()).unwrap();
+    let mut att = Attendee::from(legacy.clone());
+    att.reg_index = next_reg_index(e, id);
+    write_attendee(e, id, att);
+    journal::remove(e, id.clone());
+
+    let registered: u32 = e.storage().get_unchecked(DataKey::Registered).unwrap();
+    journal::set(e, DataKey::Registered, registered + 1);
+
+    if legacy.attended {
+        let attended_fees = get_attended_fees(e);
+        journal::set(e, DataKey::AttFees, attended_fees + legacy.fee);
+    }
+    if legacy.refunded {
+        let refunds: u32 = e.storage().get_unchecked(DataKey::Refunds).unwrap();
+        journal::set(e, DataKey::Refunds, refunds + 1);
+    }
+    true
+}
+
+fn allowlist_enabled(e: &Env) -> bool {
+    e.storage().has(DataKey::AllowMode) && e.storage().get_unchecked(DataKey::AllowMode).unwrap()
+}
+
+fn read_tier(e: &Env, tier: u32) -> Tier {
+    if !e.storage().has(DataKey::Tier(tier)) {
+        panic!("tier does not exist");
+    }
+    e.storage().get_unchecked(DataKey::Tier(tier)).unwrap()
+}
+
+// Everyone checked in: paid attendees, comped ones and late arrivals
+fn get_attended(e: &Env) -> u32 {
+    get_count(e) + get_comp_attended(e) + get_late(e)
+}
+
+fn get_late(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::Late) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Late).unwrap()
+}
+
+fn get_total_time(e: &Env) -> u64 {
+    if !e.storage().has(DataKey::TotalTime) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::TotalTime).unwrap()
+}
+
+fn get_grace(e: &Env) -> u64 {
+    if !e.storage().has(DataKey::Grace) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Grace).unwrap()
+}
+
+// Past the scheduled check-in close, in the grace period
+fn is_late(e: &Env) -> bool {
+    match get_schedule(e) {
+        Some(schedule) => e.ledger().timestamp() >= schedule.claim_open,
+        None => false
+    }
+}
+
+fn get_comp_attended(e: &Env) -> u32 {
+    e.storage().get_unchecked(DataKey::CompAtt).unwrap()
+}
+
+fn get_held(e: &Env) -> i128 {
+    e.storage().get_unchecked(DataKey::Held).unwrap()
+}
+
+// Hand out the next registration index
+fn next_reg_index(e: &Env, id: &Identifier) -> u32 {
+    let index: u32 = e.storage().get_unchecked(DataKey::NextReg).unwrap();
+    journal::set(e, DataKey::NextReg, index + 1);
+    journal::set(e, DataKey::RegId(index), id.clone());
+    index
+}
+
+// Mint one unit of the receipt token to a new registrant, if receipts are enabled
+fn issue_receipt(e: &Env, id: &Identifier) {
+    if e.storage().has(DataKey::ReceiptTok) {
+        let receipt: BytesN<32> = e.storage().get_unchecked(DataKey::ReceiptTok).unwrap();
+        token::Client::new(e, &receipt).mint(&Signature::Invoker, &0, id, &1);
+    }
+}
+
+// Burn the receipt at check-in or cancellation; fails if the registrant no longer holds it
+fn take_receipt(e: &Env, id: &Identifier) {
+    if e.storage().has(DataKey::ReceiptTok) {
+        let receipt: BytesN<32> = e.storage().get_unchecked(DataKey::ReceiptTok).unwrap();
+        token::Client::new(e, &receipt).burn(&Signature::Invoker, &0, id, &1);
+    }
+}
+
+fn has_administrator(e: &Env) -> bool {
+    let key = DataKey::Admin;
+    e.storage().has(key)
+}
+
+fn read_administrator(e: &Env) -> Identifier {
+    let key = DataKey::Admin;
+    e.storage().get_unchecked(key).unwrap()
+}
+
+fn write_administrator(e: &Env, id: Identifier) {
+    let key = DataKey::Admin;
+    journal::set(e, key, id);
+}
+
+fn get_audit_count(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::AuditCount) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::AuditCount).unwrap()
+}
+
+// Append an admin operation to the audit log, overwriting the oldest entry once it's full
+fn log_admin_action(e: &Env, action: Symbol, target: Option<Identifier>) {
+    let count = get_audit_count(e);
+    let entry = AuditEntry {
+        action,
+        actor: e.invoker().into(),
+        target,
+        timestamp: e.ledger().timestamp()
+    };
+    journal::set(e, DataKey::AuditLog(count % AUDIT_LOG_SIZE), entry);
+    journal::set(e, DataKey::AuditCount, count + 1);
+}
+
+fn has_approvers(e: &Env) -> bool {
+    e.storage().has(DataKey::Approvers)
+}
+
+fn get_timelock(e: &Env) -> u64 {
+    if !e.storage().has(DataKey::Timelock) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Timelock).unwrap()
+}
+
+// Sensitive operations can't be called directly once approvers or a timelock are configured
+fn check_unguarded(e: &Env) {
+    if has_approvers(e) || get_timelock(e) > 0 {
+        panic!("action requires approval");
+    }
+}
+
+// Without approvers, the admin alone proposes and approves actions
+fn check_approver(e: &Env, auth_id: &Identifier) {
+    if !has_approvers(e) {
+        check_admin(e, auth_id);
+        return;
+    }
+    let approvers: Vec<Identifier> = e.storage().get_unchecked(DataKey::Approvers).unwrap();
+    if !approvers.contains(auth_id) {
+        panic!("not authorized by approver");
+    }
+}
+
+pub fn check_admin(e: &Env, auth_id: &Identifier) {
+    if *auth_id != read_administrator(e) {
+        panic!("not authorized by admin")
+    }
+}
+
+// Disputes are settled by the arbiter named at initialization, or by the admin if there is none
+pub fn check_arbiter(e: &Env, auth_id: &Identifier) {
+    if !e.storage().has(DataKey::Arbiter) {
+        check_admin(e, auth_id);
+        return;
+    }
+    let arbiter: Identifier = e.storage().get_unchecked(DataKey::Arbiter).unwrap();
+    if *auth_id != arbiter {
+        panic!("not authorized by arbiter")
+    }
+}
+
+// Payout batches may also be run by the automation key, which can do nothing else
+pub fn check_operator(e: &Env, auth_id: &Identifier) {
+    if e.storage().has(DataKey::AutoKey) {
+        let key: Identifier = e.storage().get_unchecked(DataKey::AutoKey).unwrap();
+        if *auth_id == key {
+            return;
+        }
+    }
+    check_admin(e, auth_id);
+}
+
+// Finalize in kind on behalf of the admin; the automation key only pays out finalized events
+fn ensure_finalized_by(e: &Env, auth_id: &Identifier) {
+    if *auth_id != read_administrator(e) && !is_finalized(e) {
+        fail(Error::EventNotFinalized);
+    }
+    ensure_finalized(e);
+}
+
+#[contractimpl]
+impl DistributionContract {
+
+    pub fn initialize(
+        e: Env,
+        config: InitConfig
+    ) {
+        if has_administrator(&e) {
+            panic!("admin is already set");
+        }
+
+        let decimals = token::Client::new(&e, &config.token).decimals();
+        journal::set(&e, DataKey::Decimals, decimals);
+        if config.whole {
+            journal::set(&e, DataKey::WholeUnits, true);
+        }
+
+        let price = admin_amount(&e, config.price);
+        check_price(price);
+        if let Some((min, max)) = config.price_bounds {
+            write_price_bounds(&e, admin_amount(&e, min), admin_amount(&e, max));
+        }
+        check_price_bounds(&e, price);
+        write_administrator(&e, config.admin);
+
+        journal::set(&e, DataKey::Price, price);
+        journal::set(&e, DataKey::Token, config.token);
+        journal::set(&e, DataKey::Unclaimed, 0 as i128);
+        journal::set(&e, DataKey::Withdraws, Vec::<Identifier>::new(&e));
+        journal::set(&e, DataKey::Registered, 0 as u32);
+        journal::set(&e, DataKey::NextReg, 0 as u32);
+        journal::set(&e, DataKey::Remainder, config.remainder.unwrap_or(RemainderPolicy::Retain));
+        journal::set(&e, DataKey::StorageVer, STORAGE_VERSION);
+        journal::set(&e, DataKey::AttFees, 0 as i128);
+        journal::set(&e, DataKey::PaidOut, 0 as i128);
+        journal::set(&e, DataKey::Refunds, 0 as u32);
+        journal::set(&e, DataKey::Tiers, Vec::<u32>::new(&e));
+        journal::set(&e, DataKey::Held, 0 as i128);
+        journal::set(&e, DataKey::ProposalN, 0 as u32);
+        journal::set(&e, DataKey::Pending, Vec::<u32>::new(&e));
+        journal::set(&e, DataKey::Comped, 0 as u32);
+        journal::set(&e, DataKey::CompAtt, 0 as u32);
+        if let Some(arbiter) = config.arbiter {
+            journal::set(&e, DataKey::Arbiter, arbiter);
+        }
+        if let Some(beneficiary) = config.benef {
+            journal::set(&e, DataKey::Benef, beneficiary);
+        }
+        if let Some(deadline) = config.inst_due {
+            journal::set(&e, DataKey::InstDue, deadline);
+        }
+        if let Some(quorum) = config.quorum {
+            if quorum > 100 {
+                panic!("Invalid quorum");
+            }
+            journal::set(&e, DataKey::Quorum, quorum);
+        }
+        if let Some(order) = config.payout_order {
+            journal::set(&e, DataKey::Order, order);
+        }
+    }
+
+    // Create or reprice a tier. Seats already sold are kept.
+    pub fn set_tier(
+        env: Env,
+        tier: u32,
+        price: i128,
+        capacity: u32
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_tier"), None);
+        check_not_finalized(&env);
+        let price = admin_amount(&env, price);
+        check_price(price);
+        check_price_bounds(&env, price);
+
+        let mut sold = 0;
+        if env.storage().has(DataKey::Tier(tier)) {
+            sold = read_tier(&env, tier).sold;
+        } else {
+            let mut tiers: Vec<u32> = env.storage().get_unchecked(DataKey::Tiers).unwrap();
+            tiers.push_back(tier);
+            journal::set(&env, DataKey::Tiers, tiers);
+        }
+
+        if capacity < sold {
+            panic!("capacity below seats sold");
+        }
+        journal::set(&env, DataKey::Tier(tier), Tier { price, capacity, sold });
+    }
+
+    // Charge `price` until `until` for every `(until, price)` step, in increasing order of
+    // `until`. After the last step the base price applies. Ignored while an oracle sets the price.
+    pub fn set_steps(
+        env: Env,
+        steps: Vec<(u64, i128)>
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("steps"), None);
+        check_not_finalized(&env);
+
+        let mut scaled = Vec::new(&env);
+        let mut last = 0;
+        for step in steps.iter() {
+            let (until, price) = step.unwrap();
+            if until <= last
+            {
+                panic!("Invalid price steps")
+            }
+            last = until;
+            let price = admin_amount(&env, price);
+            check_price(price);
+            check_price_bounds(&env, price);
+            scaled.push_back((until, price));
+        }
+        journal::set(&env, DataKey::PriceSteps, scaled);
+    }
+
+    // When the price in effect changes next and the base price after it, `None` past the
+    // last step
+    pub fn next_price(env: Env) -> Option<(u64, i128)> {
+        let step = current_step(&env)?;
+        let steps = get_price_steps(&env);
+        let (until, _) = steps.get_unchecked(step).unwrap();
+        match steps.get(step + 1) {
+            Some(next) => Some((until, next.unwrap().1)),
+            None => Some((until, get_price(&env)))
+        }
+    }
+
+    // Allow registrations up to `overbook_percent` of `capacity`. Once `capacity` attendees
+    // have checked in, later arrivals are refunded in full instead.
+    pub fn set_cap(
+        env: Env,
+        capacity: u32,
+        overbook_percent: u32
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("capacity"), None);
+        check_not_finalized(&env);
+        if overbook_percent < 100
+        {
+            panic!("Invalid overbooking")
+        }
+        journal::set(&env, DataKey::Capacity, capacity);
+        journal::set(&env, DataKey::Overbook, overbook_percent);
+    }
+
+    pub fn decimals(env: Env) -> u32 {
+        get_decimals(&env)
+    }
+
+    pub fn bounds(env: Env) -> Option<(i128, i128)> {
+        get_price_bounds(&env)
+    }
+
+    pub fn get_tier(
+        env: Env,
+        tier: u32
+    ) -> Tier {
+        read_tier(&env, tier)
+    }
+
+    // Remaining seats of every tier as (tier, available) pairs
+    pub fn tier_avail(env: Env) -> Vec<(u32, u32)> {
+        let tiers: Vec<u32> = env.storage().get_unchecked(DataKey::Tiers).unwrap();
+        let mut availability = Vec::new(&env);
+        for tier_id in tiers.iter() {
+            let tier_id = tier_id.unwrap();
+            let stored_tier = read_tier(&env, tier_id);
+            availability.push_back((tier_id, stored_tier.capacity - stored_tier.sold));
+        }
+        availability
+    }
+
+    pub fn set_remain(
+        env: Env,
+        policy: RemainderPolicy
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_remain"), None);
+        check_unguarded(&env);
+        write_remainder_policy(&env, policy);
+    }
+
+    // Withdrawal indexes follow attendance order until finalization, which reorders them by `order`.
+    // Indexes returned by `attend` are only final under `PayoutOrder::Attendance`.
+    pub fn set_order(
+        env: Env,
+        order: PayoutOrder
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_order"), None);
+        check_not_finalized(&env);
+        journal::set(&env, DataKey::Order, order);
+    }
+
+    pub fn order(env: Env) -> PayoutOrder {
+        get_payout_order(&env)
+    }
+
+    // Let a bot drive `withdraw`, `payout_one` and `refund` without holding the admin key.
+    // The bot can't finalize, so the admin has to. Replaces any previous automation key.
+    pub fn set_auto(
+        env: Env,
+        id: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("auto_key"), Some(id.clone()));
+        journal::set(&env, DataKey::AutoKey, id);
+    }
+
+    pub fn set_benef(
+        env: Env,
+        beneficiary: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_benef"), Some(beneficiary.clone()));
+        check_unguarded(&env);
+        journal::set(&env, DataKey::Benef, beneficiary);
+    }
+
+    // Share the beneficiary proceeds (sweeps and routed surcharges) between co-organizers.
+    // Weights are in basis points and must add up to 10000.
+    pub fn set_splits(
+        env: Env,
+        splits: Vec<(Identifier, u32)>
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_splits"), None);
+        check_unguarded(&env);
+
+        if splits.len() == 0 || splits.len() > MAX_BATCH
+        {
+            panic!("Invalid range")
+        }
+
+        let mut total = 0;
+        for split in splits.iter() {
+            let (_, bps) = split.unwrap();
+            total += bps;
+        }
+        if total != 10_000
+        {
+            panic!("Invalid split")
+        }
+        journal::set(&env, DataKey::Splits, splits);
+    }
+
+    // Pay the proceeds owed to co-organizers by their weights. Anyone can call it.
+    pub fn pay_fees(env: Env) -> i128 {
+        if !env.storage().has(DataKey::Splits)
+        {
+            panic!("splits not set")
+        }
+
+        let owed = get_fees_owed(&env);
+        let splits: Vec<(Identifier, u32)> = env.storage().get_unchecked(DataKey::Splits).unwrap();
+        let token = get_token(&env);
+        journal::set(&env, DataKey::FeesOwed, 0 as i128);
+
+        // The last co-organizer gets what rounding left over
+        let mut left = owed;
+        for i in 0..splits.len() {
+            let (to, bps) = splits.get_unchecked(i).unwrap();
+            let mut amount = mul_div(owed, bps as i128, 10_000);
+            if i == splits.len() - 1 {
+                amount = left;
+            }
+            left -= amount;
+            transfer_from_contract_to_account(&env, &token, &to, &amount, symbol!("fee"));
+            env.events().publish(EVT_V1_SWEEP, TransferEvent { to, amount });
+        }
+        owed
+    }
+
+    // When enabled, only identifiers on the allowlist can deposit
+    pub fn set_allow(
+        env: Env,
+        enabled: bool
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("allow_mode"), None);
+        check_not_finalized(&env);
+        journal::set(&env, DataKey::AllowMode, enabled);
+    }
+
+    pub fn allow(
+        env: Env,
+        id: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("allow"), Some(id.clone()));
+        set_tracked(&env, DataKey::Allowed(id), true);
+    }
+
+    // Let the phases follow the clock: registration closes at `registration_close`, check-ins
+    // run from `event_start` until `claim_open`, and payouts are taken until `claim_close`.
+    pub fn set_sched(
+        env: Env,
+        schedule: Schedule
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("schedule"), None);
+        check_not_finalized(&env);
+        if schedule.reg_end > schedule.claim_open
+            || schedule.event_at > schedule.claim_open
+            || schedule.claim_open > schedule.claim_end
+        {
+            panic!("Invalid schedule")
+        }
+        journal::set(&env, DataKey::Schedule, schedule);
+    }
+
+    // Record that `attendee` left. With `DistributionMode::ByTime` the forfeited pool is
+    // split by the time each attendee was present.
+    pub fn check_out(
+        env: Env,
+        attendee: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("checkout"), Some(attendee.clone()));
+        check_not_finalized(&env);
+
+        if !has_attendee(&env, &attendee)
+        {
+            panic!("attendee did not register")
+        }
+        let mut att = read_attendee(&env, &attendee);
+        if !att.attended() || att.comped() || att.late()
+        {
+            panic!("not checked in")
+        }
+        if att.left_at != 0
+        {
+            panic!("already checked out")
+        }
+
+        att.left_at = env.ledger().timestamp();
+        let present = att.left_at - att.arrived_at;
+        write_attendee(&env, &attendee, att);
+        journal::set(&env, DataKey::TotalTime, get_total_time(&env) + present);
+        env.events().publish((symbol!("checkout"),), attendee);
+    }
+
+    // Keep taking check-ins for `seconds` after the scheduled check-in close. Late arrivals
+    // are refunded their deposit but get no share of the forfeited pool.
+    pub fn set_grace(
+        env: Env,
+        seconds: u64
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("grace"), None);
+        check_not_finalized(&env);
+        journal::set(&env, DataKey::Grace, seconds);
+    }
+
+    // Hand registrants one unit of `receipt` as a visible record of their registration, burned
+    // again at check-in. This contract must be the admin of `receipt`.
+    pub fn set_rcpt(
+        env: Env,
+        receipt: BytesN<32>
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("receipt"), None);
+        let registrations: u32 = env.storage().get_unchecked(DataKey::NextReg).unwrap();
+        if registrations > 0
+        {
+            panic!("registrations already taken")
+        }
+        journal::set(&env, DataKey::ReceiptTok, receipt);
+    }
+
+    pub fn set_swap(
+        env: Env,
+        swap: BytesN<32>
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_swap"), None);
+        journal::set(&env, DataKey::Swap, swap);
+    }
+
+    // Link this event to a `parent` event (another deployment of this contract), taking
+    // `discount_bps` off the price for anyone already registered there
+    pub fn set_parent(
+        env: Env,
+        parent: BytesN<32>,
+        discount_bps: u32
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("parent"), None);
+        check_not_finalized(&env);
+
+        if discount_bps > 10_000 || parent == env.get_current_contract()
+        {
+            panic!("Invalid parent")
+        }
+        journal::set(&env, DataKey::Parent, (parent, discount_bps));
+    }
+
+    // Only take registrations `attestor` reports as eligible, to keep sock puppets from
+    // farming a sponsored pool
+    pub fn set_attest(
+        env: Env,
+        attestor: BytesN<32>
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("attestor"), None);
+        check_not_finalized(&env);
+        journal::set(&env, DataKey::Attestor, attestor);
+    }
+
+    // Approve a charity as a target of `donate`
+    pub fn add_donee(
+        env: Env,
+        charity: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("charity"), Some(charity.clone()));
+        set_tracked(&env, DataKey::Charity(charity), true);
+    }
+
+    pub fn unallow(
+        env: Env,
+        id: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("disallow"), Some(id.clone()));
+        journal::remove(&env, DataKey::Allowed(id));
+    }
+
+    pub fn allow_n(
+        env: Env,
+        ids: Vec<Identifier>
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("allow"), None);
+        for id in ids.iter() {
+            set_tracked(&env, DataKey::Allowed(id.unwrap()), true);
+        }
+    }
+
+    pub fn unallow_n(
+        env: Env,
+        ids: Vec<Identifier>
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("disallow"), None);
+        for id in ids.iter() {
+            journal::remove(&env, DataKey::Allowed(id.unwrap()));
+        }
+    }
+
+    // Ban an identifier from depositing. If it is already registered, the
+    // paid fee is refunded and the registration removed.
+    pub fn block(
+        env: Env,
+        id: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("block"), Some(id.clone()));
+        set_tracked(&env, DataKey::Blocked(id.clone()), true);
+
+        if !has_attendee(&env, &id) {
+            return;
+        }
+        check_not_finalized(&env);
+
+        let stored_att = read_attendee(&env, &id);
+        if stored_att.attended()
+        {
+            panic!("attendance already recorded")
+        }
+
+        cancel_registration(&env, &id, stored_att);
+    }
+
+    pub fn unblock(
+        env: Env,
+        id: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("unblock"), Some(id.clone()));
+        journal::remove(&env, DataKey::Blocked(id));
+    }
+
+    pub fn set_pool(
+        env: Env,
+        pool: BytesN<32>
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_pool"), None);
+        check_not_finalized(&env);
+
+        if get_supplied(&env) > 0
+        {
+            panic!("deposits are supplied")
+        }
+        journal::set(&env, DataKey::YieldPool, pool);
+    }
+
+    // Put `amount` of the idle deposits to work in the yield pool until finalization, which
+    // redeems them. Keep enough in the contract for attendees who may still exit.
+    pub fn supply(
+        env: Env,
+        amount: i128
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("supply"), None);
+        check_not_finalized(&env);
+
+        if !env.storage().has(DataKey::YieldPool)
+        {
+            panic!("yield pool not set")
+        }
+
+        if amount <= 0 || amount > get_held(&env)
+        {
+            panic!("Invalid amount")
+        }
+
+        let pool: BytesN<32> = env.storage().get_unchecked(DataKey::YieldPool).unwrap();
+        let client = token::Client::new(&env, &get_token(&env));
+        client.incr_allow(&Signature::Invoker, &0, &Identifier::Contract(pool.clone()), &amount);
+        yield_pool::Client::new(&env, &pool).deposit(&get_contract_id(&env), &amount);
+
+        journal::set(&env, DataKey::Held, get_held(&env) - amount);
+        ledger::record(&env, symbol!("supply"), Identifier::Contract(pool), -amount);
+        journal::set(&env, DataKey::Supplied, get_supplied(&env) + amount);
+    }
+
+    // Denominate the deposit in USD (with `ORACLE_DECIMALS` precision), converted
+    // into token units by `oracle` whenever someone deposits
+    pub fn set_oracle(
+        env: Env,
+        oracle: BytesN<32>,
+        usd_price: i128
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_oracle"), None);
+        check_price(usd_price);
+        journal::set(&env, DataKey::Oracle, oracle);
+        journal::set(&env, DataKey::UsdPrice, usd_price);
+    }
+
+    // Contracts can register (e.g. a ticketing contract wrapping this one) unless disallowed here
+    pub fn set_ctrs(
+        env: Env,
+        allowed: bool
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("contracts"), None);
+        if allowed {
+            journal::remove(&env, DataKey::NoContract);
+        } else {
+            journal::set(&env, DataKey::NoContract, true);
+        }
+    }
+
+    // Unlock payouts linearly over `duration` seconds after finalization; attendees
+    // then collect them with `claim`. 0 pays everything out at once.
+    pub fn set_vest(
+        env: Env,
+        duration: u64
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("vesting"), None);
+        check_not_finalized(&env);
+        journal::set(&env, DataKey::Vesting, duration);
+    }
+
+    // Cap what each attendee receives from the forfeited deposits. The excess is
+    // sent to the beneficiary with `sweep_rem`.
+    pub fn set_bonus(
+        env: Env,
+        cap: i128
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("bonus_cap"), None);
+        check_not_finalized(&env);
+
+        if cap < 0
+        {
+            panic!("invalid cap")
+        }
+        journal::set(&env, DataKey::BonusCap, admin_amount(&env, cap));
+    }
+
+    // Number of raffle prizes funded by the forfeited deposits under `DistributionMode::Raffle`
+    pub fn set_prizes(
+        env: Env,
+        prizes: u32
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("raffle"), None);
+        check_not_finalized(&env);
+
+        if prizes > 10
+        {
+            panic!("too many prizes")
+        }
+        journal::set(&env, DataKey::Prizes, prizes);
+    }
+
+    pub fn winners(env: Env) -> Vec<Identifier> {
+        let mut winners = Vec::new(&env);
+        if !env.storage().has(DataKey::Winners) {
+            return winners;
+        }
+
+        let withdrawals = get_withdrawals(&env);
+        let indexes: Vec<u32> = env.storage().get_unchecked(DataKey::Winners).unwrap();
+        for index in indexes.iter() {
+            winners.push_back(withdrawals.get_unchecked(index.unwrap()).unwrap());
+        }
+        winners
+    }
+
+    // Match every attendee's deposit by `bps` basis points, up to `cap`, out of the sponsor pool
+    pub fn set_match(
+        env: Env,
+        bps: u32,
+        cap: i128
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_match"), None);
+        check_not_finalized(&env);
+        journal::set(&env, DataKey::MatchBps, bps);
+        journal::set(&env, DataKey::MatchCap, admin_amount(&env, cap));
+    }
+
+    // Let no identifier pay for more than `limit` registrations, counting its own, team members
+    // it registered as captain and split deposits it covers a share of
+    pub fn set_seats(
+        env: Env,
+        limit: u32
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("seats"), None);
+        check_not_finalized(&env);
+
+        if limit == 0
+        {
+            panic!("Invalid limit")
+        }
+        journal::set(&env, DataKey::SeatLimit, limit);
+    }
+
+    // Pay referrers `bonus` out of the pool for every attendee they referred who showed up
+    pub fn set_ref(
+        env: Env,
+        bonus: i128
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("ref_bonus"), None);
+        check_not_finalized(&env);
+        let bonus = admin_amount(&env, bonus);
+        if bonus < 0
+        {
+            panic!("invalid amount")
+        }
+        journal::set(&env, DataKey::RefBonus, bonus);
+    }
+
+    // Collect the referral bonus for the referred attendees who showed up. Returns the amount paid.
+    pub fn claim_refs(
+        env: Env,
+        referrer: Identifier
+    ) -> i128 {
+        let invoker: Identifier = env.invoker().into();
+        if invoker != referrer {
+            panic!("not authorized by referrer");
+        }
+        if !env.storage().has(DataKey::RefShare)
+        {
+            panic!("nothing to claim")
+        }
+        let referrals = get_referrals(&env, &referrer);
+        if referrals == 0
+        {
+            panic!("nothing to claim")
+        }
+
+        let share: i128 = env.storage().get_unchecked(DataKey::RefShare).unwrap();
+        let amount = share * referrals as i128;
+        journal::remove(&env, DataKey::Referrals(referrer.clone()));
+        transfer_from_contract_to_account(&env, &get_token(&env), &referrer, &amount, symbol!("referral"));
+        env.events().publish((symbol!("referral"),), TransferEvent { to: referrer, amount });
+        amount
+    }
+
+    // Add to the matching pool. The sponsor must have approved the contract for `amount`.
+    pub fn sponsor(
+        env: Env,
+        sponsor: Identifier,
+        amount: i128
+    ) {
+        let invoker: Identifier = env.invoker().into();
+        if invoker != sponsor {
+            panic!("not authorized by sponsor");
+        }
+        check_not_finalized(&env);
+        if amount <= 0 {
+            panic!("invalid amount");
+        }
+
+        let key = DataKey::Sponsor(sponsor.clone());
+        let mut contributed: i128 = 0;
+        if env.storage().has(key.clone()) {
+            contributed = env.storage().get_unchecked(key.clone()).unwrap();
+        }
+        set_tracked(&env, key, contributed + amount);
+        journal::set(&env, DataKey::SponsPool, get_sponsor_pool(&env) + amount);
+
+        transfer_from_account_to_contract(&env, &get_token(&env), &sponsor, &amount, symbol!("sponsor"));
+    }
+
+    pub fn sponsored(
+        env: Env,
+        sponsor: Identifier
+    ) -> i128 {
+        let key = DataKey::Sponsor(sponsor);
+        if !env.storage().has(key.clone()) {
+            return 0;
+        }
+        env.storage().get_unchecked(key).unwrap()
+    }
+
+    // Take back a contribution to an event that was cancelled. Returns the amount returned.
+    pub fn reclaim(
+        env: Env,
+        sponsor: Identifier
+    ) -> i128 {
+        let invoker: Identifier = env.invoker().into();
+        if invoker != sponsor {
+            panic!("not authorized by sponsor");
+        }
+        if !is_finalized(&env) || get_mode(&env) != DistributionMode::Refund
+        {
+            panic!("event not cancelled")
+        }
+
+        let amount = Self::sponsored(env.clone(), sponsor.clone());
+        if amount == 0
+        {
+            panic!("nothing to reclaim")
+        }
+        journal::remove(&env, DataKey::Sponsor(sponsor.clone()));
+        journal::set(&env, DataKey::SponsPool, get_sponsor_pool(&env) - amount);
+        transfer_from_contract_to_account(&env, &get_token(&env), &sponsor, &amount, symbol!("sponsor"));
+        amount
+    }
+
+    // Run a free event: registration takes no deposit, and the sponsor pool is shared
+    // equally between attendees instead of matching their fees. The price must be zero.
+    pub fn set_rsvp(
+        env: Env,
+        enabled: bool
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("rsvp"), None);
+        check_not_finalized(&env);
+        if !enabled {
+            journal::remove(&env, DataKey::Rsvp);
+            return;
+        }
+
+        if current_price(&env) != 0
+        {
+            panic!("price must be zero")
+        }
+        journal::set(&env, DataKey::Rsvp, true);
+    }
+
+    // Hold back each attendee's payout until they call `feedback`
+    pub fn set_fb_req(
+        env: Env,
+        required: bool
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("feedback"), None);
+        if required {
+            journal::set(&env, DataKey::FeedReq, true);
+        } else {
+            journal::remove(&env, DataKey::FeedReq);
+        }
+    }
+
+    // Charge `bps` extra on registrations from `starts_at` on, e.g. in the last 48 hours before the event
+    pub fn set_surchg(
+        env: Env,
+        starts_at: u64,
+        bps: u32,
+        to_beneficiary: bool
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("surcharge"), None);
+        check_not_finalized(&env);
+
+        if to_beneficiary && !has_beneficiary(&env)
+        {
+            panic!("beneficiary not set")
+        }
+        journal::set(&env, DataKey::Surcharge, Surcharge { starts_at, bps, to_benef: to_beneficiary });
+    }
+
+    // Publish a ("checkpoint") event with registered and attended counts every `n` attendances, 0 disables
+    pub fn set_chkpt(
+        env: Env,
+        n: u32
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("checkpoint"), None);
+        check_not_finalized(&env);
+        journal::set(&env, DataKey::CheckptN, n);
+    }
+
+    // Allow attendees to pay half of the price at registration and the rest
+    // via `complete` until `deadline`
+    pub fn set_inst(
+        env: Env,
+        deadline: u64
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("set_inst"), None);
+        check_not_finalized(&env);
+        journal::set(&env, DataKey::InstDue, deadline);
+    }
+
+    // Returns the registration index and the amount charged
+    // `contact_hash` commits to the attendee's contact details without putting them on chain
+    pub fn deposit(
+        env: Env,
+        attendee: Identifier,
+        contact_hash: Option<BytesN<32>>
+    ) -> (u32, i128) {
+        let registration = register(&env, attendee.clone(), None, None, 0, false);
+        if contact_hash.is_some()
+        {
+            let mut att = read_attendee(&env, &attendee);
+            att.contact = contact_hash;
+            write_attendee(&env, &attendee, att);
+        }
+        registration
+    }
+
+    // Register the user `sub_id` of the shared account `owner`, under the identifier given by
+    // `sub_ident`. The owner pays the deposit, gets all refunds and payouts and can act
+    // for the sub-id wherever the attendee could. Each (owner, sub_id) registers once.
+    pub fn dep_sub(
+        env: Env,
+        owner: Identifier,
+        sub_id: u64
+    ) -> (u32, i128) {
+        let invoker: Identifier = env.invoker().into();
+        if invoker != owner {
+            panic!("not authorized by owner");
+        }
+        let attendee = sub_identifier(&env, &owner, sub_id);
+        set_tracked(&env, DataKey::SubAcct(attendee.clone()), SubAccount { owner: owner.clone(), sub_id });
+        register(&env, attendee, None, Some(owner), 10_000, false)
+    }
+
+    pub fn sub_ident(
+        env: Env,
+        owner: Identifier,
+        sub_id: u64
+    ) -> Identifier {
+        sub_identifier(&env, &owner, sub_id)
+    }
+
+    pub fn sub_acct(
+        env: Env,
+        attendee: Identifier
+    ) -> Option<SubAccount> {
+        get_sub_account(&env, &attendee)
+    }
+
+    // Register like `deposit`, crediting `referrer`, who must be registered already
+    pub fn dep_ref(
+        env: Env,
+        attendee: Identifier,
+        referrer: Identifier
+    ) -> (u32, i128) {
+        if referrer == attendee || !has_attendee(&env, &referrer)
+        {
+            panic!("invalid referrer")
+        }
+        let registration = register(&env, attendee.clone(), None, None, 0, false);
+        set_tracked(&env, DataKey::Referrer(attendee), referrer);
+        registration
+    }
+
+    // Register with `payer` (e.g. an employer) covering `payer_share_bps` of the deposit
+    // and the attendee the rest. Refunds and payouts are split back in the same proportions.
+    pub fn dep_split(
+        env: Env,
+        attendee: Identifier,
+        payer: Identifier,
+        payer_share_bps: u32
+    ) -> (u32, i128) {
+        let invoker: Identifier = env.invoker().into();
+        if invoker != payer {
+            panic!("not authorized by payer");
+        }
+        if payer_share_bps > 10_000
+        {
+            panic!("Invalid share")
+        }
+        register(&env, attendee, None, Some(payer), payer_share_bps, false)
+    }
+
+    // Register a team whose captain pays every member's deposit. Attendance is
+    // tracked per member, and refunds and payouts go back to the captain.
+    pub fn reg_team(
+        env: Env,
+        captain: Identifier,
+        members: Vec<Identifier>,
+        team_name: Symbol
+    ) {
+        let invoker: Identifier = env.invoker().into();
+        if invoker != captain {
+            panic!("not authorized by captain");
+        }
+        if members.len() == 0 || members.len() > MAX_BATCH
+        {
+            panic!("Invalid range")
+        }
+
+        if env.storage().has(DataKey::Team(team_name.clone()))
+        {
+            panic!("team already registered")
+        }
+
+        for member in members.iter() {
+            register(&env, member.unwrap(), None, Some(captain.clone()), 10_000, false);
+        }
+        set_tracked(&env, DataKey::Team(team_name), Team { captain, members });
+    }
+
+    pub fn get_team(
+        env: Env,
+        team_name: Symbol
+    ) -> Team {
+        if !env.storage().has(DataKey::Team(team_name.clone()))
+        {
+            panic!("team does not exist")
+        }
+        env.storage().get_unchecked(DataKey::Team(team_name)).unwrap()
+    }
+
+    // Report a transfer of `amount` the invoker just made to this contract, crediting it to
+    // them for `prepaid`. Only tokens that are neither held nor credited yet count.
+    pub fn notify_pay(
+        env: Env,
+        from: Identifier,
+        amount: i128
+    ) {
+        let invoker: Identifier = env.invoker().into();
+        if invoker != from {
+            panic!("not authorized by sender");
+        }
+        if amount <= 0
+        {
+            panic!("amount must be positive")
+        }
+
+        let client = token::Client::new(&env, &get_token(&env));
+        let credited = get_credited(&env);
+        if client.balance(&get_contract_id(&env)) - get_held(&env) - credited < amount
+        {
+            panic!("payment not received")
+        }
+        set_tracked(&env, DataKey::Credit(from.clone()), get_credit(&env, &from) + amount);
+        journal::set(&env, DataKey::Credited, credited + amount);
+    }
+
+    // Register with the credit from `notify_pay`, without an allowance. The credit must
+    // cover `quote`; anything above it is sent back.
+    pub fn prepaid(
+        env: Env,
+        attendee: Identifier
+    ) -> (u32, i128) {
+        check_attendee_auth(&env, &attendee);
+        let credit = get_credit(&env, &attendee);
+        let amount = Self::quote(env.clone(), attendee.clone(), None);
+        if credit < amount
+        {
+            panic!("payment not received")
+        }
+        journal::remove(&env, DataKey::Credit(attendee.clone()));
+        journal::set(&env, DataKey::Credited, get_credited(&env) - credit);
+
+        let registration = register(&env, attendee.clone(), None, None, 0, true);
+        if credit > amount
+        {
+            journal::set(&env, DataKey::Held, get_held(&env) + credit - amount);
+            transfer_from_contract_to_account(&env, &get_token(&env), &attendee, &(credit - amount), symbol!("refund"));
+        }
+        registration
+    }
+
+    pub fn credit(
+        env: Env,
+        id: Identifier
+    ) -> i128 {
+        get_credit(&env, &id)
+    }
+
+    // Register in a single confirmation: `auth` is the attendee's signature authorizing the
+    // token to transfer the quoted amount to this contract, so no allowance is needed first
+    pub fn dep_xfer(
+        env: Env,
+        attendee: Identifier,
+        auth: Signature,
+        nonce: i128
+    ) -> (u32, i128) {
+        let signer = match &auth {
+            Signature::Ed25519(sig) => Identifier::Ed25519(sig.public_key.clone()),
+            Signature::Account(sigs) => Identifier::Account(sigs.account_id.clone()),
+            Signature::Invoker => panic!("transfer must be signed"),
+        };
+        if signer != attendee {
+            panic!("not authorized by attendee");
+        }
+
+        let amount = Self::quote(env.clone(), attendee.clone(), None);
+        token::Client::new(&env, &get_token(&env)).xfer(&auth, &nonce, &get_contract_id(&env), &amount);
+        register(&env, attendee, None, None, 0, true)
+    }
+
+    // Amount `deposit` (or `dep_tier` when `tier` is set) would charge `attendee` right now,
+    // so wallets can request the matching allowance
+    pub fn quote(
+        env: Env,
+        attendee: Identifier,
+        tier: Option<u32>
+    ) -> i128 {
+        check_can_register(&env, &attendee);
+        let price = bundle_price(&env, &attendee, registration_price(&env, tier));
+        upfront_amount(&env, price) + surcharge_amount(&env, price)
+    }
+
+    // Run every check `deposit` (or `dep_tier` with `tier`) would, including the allowance
+    // given to this contract, without registering
+    pub fn can_dep(
+        env: Env,
+        attendee: Identifier,
+        tier: Option<u32>
+    ) -> Result<(), Error> {
+        if let Some(error) = registration_error(&env, &attendee) {
+            return Err(error);
+        }
+        if !seat_available(&env, &attendee) {
+            return Err(Error::SeatLimit);
+        }
+        if let Some(tier_id) = tier {
+            if !env.storage().has(DataKey::Tier(tier_id)) {
+                return Err(Error::NoTier);
+            }
+            let stored_tier = read_tier(&env, tier_id);
+            if stored_tier.sold >= stored_tier.capacity {
+                return Err(Error::TierSoldOut);
+            }
+        }
+
+        let price = bundle_price(&env, &attendee, registration_price(&env, tier));
+        let amount = upfront_amount(&env, price) + surcharge_amount(&env, price);
+        let allowance = token::Client::new(&env, &get_token(&env)).allowance(&attendee, &get_contract_id(&env));
+        if allowance < amount {
+            return Err(Error::AllowanceTooLow);
+        }
+        Ok(())
+    }
+
+    // Run every check `attend` would for `attendee`, without recording anything
+    pub fn can_attend(
+        env: Env,
+        attendee: Identifier
+    ) -> Result<(), Error> {
+        match attendance_error(&env, &attendee) {
+            Some(error) => Err(error),
+            None => Ok(())
+        }
+    }
+
+    // Register at the price of `tier`, subject to its capacity
+    pub fn dep_tier(
+        env: Env,
+        attendee: Identifier,
+        tier: u32
+    ) -> (u32, i128) {
+        register(&env, attendee, Some(tier), None, 0, false)
+    }
+
+    // Move to `new_tier` before finalization, paying the difference to the current price of the
+    // tier registered with, or getting it back when downgrading
+    pub fn move_tier(
+        env: Env,
+        attendee: Identifier,
+        new_tier: u32
+    ) -> i128 {
+        check_attendee_auth(&env, &attendee);
+        check_not_finalized(&env);
+
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        let mut stored_att = read_attendee(&env, &attendee);
+        if stored_att.comped()
+        {
+            panic!("attendee is comped")
+        }
+
+        if stored_att.due > 0
+        {
+            panic!("deposit not complete")
+        }
+
+        if stored_att.tier == Some(new_tier)
+        {
+            panic!("already in tier")
+        }
+
+        let new_price = registration_price(&env, Some(new_tier));
+        let mut old_price = current_price(&env);
+        if let Some(old_tier) = stored_att.tier {
+            let mut stored_tier = read_tier(&env, old_tier);
+            old_price = stored_tier.price;
+            stored_tier.sold -= 1;
+            journal::set(&env, DataKey::Tier(old_tier), stored_tier);
+        }
+        let mut stored_tier = read_tier(&env, new_tier);
+        stored_tier.sold += 1;
+        journal::set(&env, DataKey::Tier(new_tier), stored_tier);
+
+        let delta = new_price - old_price;
+        if stored_att.fee + delta < 0
+        {
+            panic!("Invalid amount")
+        }
+        stored_att.fee += delta;
+        stored_att.tier = Some(new_tier);
+        if stored_att.attended() {
+            let attended_fees = get_attended_fees(&env);
+            journal::set(&env, DataKey::AttFees, attended_fees + delta);
+        } else {
+            journal::set(&env, DataKey::Unclaimed, get_unclaimed(&env) + delta);
+        }
+        write_attendee(&env, &attendee, stored_att.clone());
+
+        let token = get_token(&env);
+        if delta > 0 {
+            collect(&env, &token, &attendee, &stored_att, delta, symbol!("tier"));
+        } else {
+            pay_back(&env, &token, &attendee, &stored_att, -delta, symbol!("tier"));
+        }
+        delta
+    }
+
+    // Add `amount` to the attendee's deposit, e.g. a donation. It is refunded and
+    // counts towards pro rata shares like the rest of the fee.
+    pub fn top_up(
+        env: Env,
+        attendee: Identifier,
+        amount: i128
+    ) {
+        check_attendee_auth(&env, &attendee);
+        check_not_finalized(&env);
+
+        if amount <= 0
+        {
+            panic!("Invalid amount")
+        }
+
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        let mut stored_att = read_attendee(&env, &attendee);
+        if stored_att.comped()
+        {
+            panic!("attendee is comped")
+        }
+
+        stored_att.fee += amount;
+        if stored_att.attended() {
+            let attended_fees = get_attended_fees(&env);
+            journal::set(&env, DataKey::AttFees, attended_fees + amount);
+        } else {
+            journal::set(&env, DataKey::Unclaimed, get_unclaimed(&env) + amount);
+        }
+        write_attendee(&env, &attendee, stored_att.clone());
+
+        collect(&env, &get_token(&env), &attendee, &stored_att, amount, symbol!("top_up"));
+    }
+
+    // Pay the remainder of an installment deposit. Deposits that are not
+    // completed in time can't be marked attended and are forfeited into the pool.
+    pub fn complete(
+        env: Env,
+        attendee: Identifier
+    ) {
+        check_not_finalized(&env);
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        let mut stored_att : Attendee = read_attendee(&env, &attendee);
+        if stored_att.due == 0
+        {
+            panic!("deposit already complete")
+        }
+
+        if !installments_open(&env)
+        {
+            panic!("installment deadline passed")
+        }
+
+        let amount = stored_att.due;
+        stored_att.fee += amount;
+        stored_att.due = 0;
+        write_attendee(&env, &attendee, stored_att.clone());
+
+        let mut unclaimed: i128 = get_unclaimed(&env);
+        unclaimed += amount;
+        journal::set(&env, DataKey::Unclaimed, unclaimed);
+
+        let token = get_token(&env);
+        collect(&env, &token, &attendee, &stored_att, amount, symbol!("deposit"));
+    }
+    
+    // While a proposed sensitive action waits for its timelock, attendees can
+    // leave and take their deposit back. Only possible before finalization.
+    pub fn exit(
+        env: Env,
+        attendee: Identifier
+    ) {
+        check_attendee_auth(&env, &attendee);
+        check_not_finalized(&env);
+
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        if !in_exit_window(&env) {
+            panic!("no pending action to exit from");
+        }
+
+        let stored_att = read_attendee(&env, &attendee);
+        check_not_frozen(&stored_att);
+        cancel_registration(&env, &attendee, stored_att);
+    }
+
+    // Record the hash of the attendee's survey receipt, unlocking their payout when feedback is required
+    pub fn feedback(
+        env: Env,
+        attendee: Identifier,
+        feedback_hash: BytesN<32>
+    ) {
+        check_attendee_auth(&env, &attendee);
+
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        let mut stored_att = read_attendee(&env, &attendee);
+        if !stored_att.attended()
+        {
+            panic!("attendee did not attend")
+        }
+
+        stored_att.feedback = Some(feedback_hash);
+        write_attendee(&env, &attendee, stored_att);
+    }
+
+    // Link the registration to a conventional ticket so check-in staff can look it up by
+    // scanning it. Rebinding replaces the attendee's previous ticket.
+    pub fn tie_ticket(
+        env: Env,
+        attendee: Identifier,
+        ticket_id: BytesN<32>
+    ) {
+        let invoker: Identifier = env.invoker().into();
+        if invoker == read_administrator(&env) {
+            log_admin_action(&env, symbol!("bind"), Some(attendee.clone()));
+        } else if !is_attendee_auth(&env, &invoker, &attendee) {
+            panic!("not authorized by attendee");
+        }
+
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        if env.storage().has(DataKey::Ticket(ticket_id.clone()))
+        {
+            panic!("ticket already bound")
+        }
+
+        unbind_ticket(&env, &attendee);
+        journal::set(&env, DataKey::Ticket(ticket_id.clone()), attendee.clone());
+        journal::set(&env, DataKey::TicketOf(attendee), ticket_id);
+    }
+
+    pub fn by_ticket(
+        env: Env,
+        ticket_id: BytesN<32>
+    ) -> Identifier {
+        if !env.storage().has(DataKey::Ticket(ticket_id.clone()))
+        {
+            panic!("ticket not bound")
+        }
+        env.storage().get_unchecked(DataKey::Ticket(ticket_id)).unwrap()
+    }
+
+    // Hold the attendee's payouts and cancellation while investigating fraud
+    pub fn freeze(
+        env: Env,
+        attendee: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("freeze"), Some(attendee.clone()));
+        set_frozen(&env, &attendee, true);
+    }
+
+    pub fn unfreeze(
+        env: Env,
+        attendee: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("unfreeze"), Some(attendee.clone()));
+        set_frozen(&env, &attendee, false);
+    }
+
+    // Close the case of an attendee still frozen after the sweep against them: the payout
+    // reserved for them goes to the beneficiary. Returns the amount released.
+    pub fn release(
+        env: Env,
+        attendee: Identifier
+    ) -> i128 {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("release"), Some(attendee.clone()));
+        check_unguarded(&env);
+        release_reserve(&env, &attendee)
+    }
+
+    // Admit a speaker, sponsor or other guest without a deposit
+    pub fn comp(
+        env: Env,
+        attendee: Identifier
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("comp"), Some(attendee.clone()));
+        check_can_register(&env, &attendee);
+
+        let mut attendee_struct = Attendee {
+            fee: 0,
+            due: 0,
+            flags: 0,
+            evidence: None,
+            tier: None,
+            reg_index: next_reg_index(&env, &attendee),
+            claimed: 0,
+            feedback: None,
+            payer: None,
+            payer_bps: 0,
+            contact: None,
+            arrived_at: 0,
+            left_at: 0,
+            princ_paid: 0,
+            bonus_paid: 0
+        };
+        attendee_struct.set_flag(FLAG_COMPED);
+        write_attendee(&env, &attendee, attendee_struct);
+        issue_receipt(&env, &attendee);
+
+        let registered: u32 = env.storage().get_unchecked(DataKey::Registered).unwrap();
+        journal::set(&env, DataKey::Registered, registered + 1);
+
+        let comped: u32 = env.storage().get_unchecked(DataKey::Comped).unwrap();
+        journal::set(&env, DataKey::Comped, comped + 1);
+    }
+
+    // Returns the attendee's withdrawal index, or `NO_WITHDRAWAL_INDEX` for comped attendees
+    pub fn attend(
+        env: Env,
+        attendee: Identifier,
+        evidence: Option<BytesN<32>>
+    ) -> u32 {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("attend"), Some(attendee.clone()));
+        let stored_att = check_can_attend(&env, &attendee);
+        record_attendance(&env, attendee, stored_att, evidence)
+    }
+
+    // Start a new check-in window and return the nonce to display at the venue.
+    // Each call replaces the previous nonce.
+    pub fn open_nonce(env: Env) -> BytesN<32> {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("nonce"), None);
+        check_not_finalized(&env);
+
+        let mut data = Bytes::new(&env);
+        if env.storage().has(DataKey::Nonce)
+        {
+            let (prev, _): (BytesN<32>, u64) = env.storage().get_unchecked(DataKey::Nonce).unwrap();
+            data.append(&prev.into());
+        }
+        data.extend_from_array(&env.ledger().timestamp().to_be_bytes());
+        data.extend_from_array(&env.ledger().sequence().to_be_bytes());
+        let nonce = env.crypto().sha256(&data);
+
+        journal::set(&env, DataKey::Nonce, (nonce.clone(), env.ledger().timestamp() + NONCE_TTL));
+        nonce
+    }
+
+    // Self check-in: `sig` is the attendee's ed25519 signature over the current venue nonce
+    pub fn attend_own(
+        env: Env,
+        attendee: Identifier,
+        sig: BytesN<64>
+    ) -> u32 {
+        let key = ed25519_key(&attendee);
+        if !env.storage().has(DataKey::Nonce)
+        {
+            panic!("no check-in nonce")
+        }
+        let (nonce, expires): (BytesN<32>, u64) = env.storage().get_unchecked(DataKey::Nonce).unwrap();
+        if env.ledger().timestamp() > expires
+        {
+            panic!("check-in nonce expired")
+        }
+        env.crypto().ed25519_verify(&key, &nonce.into(), &sig);
+
+        let stored_att = check_can_attend(&env, &attendee);
+        record_attendance(&env, attendee, stored_att, None)
+    }
+
+    // Submit check-ins collected offline. Each entry carries the attendee's ed25519 signature
+    // over this contract's id. Returns the withdrawal indexes assigned, as `attend` does.
+    pub fn attend_sig(
+        env: Env,
+        entries: Vec<(Identifier, BytesN<64>)>
+    ) -> Vec<u32> {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("attend_sig"), None);
+        check_range(entries.len(), 0);
+
+        let message: Bytes = env.get_current_contract().into();
+        let mut indexes = Vec::new(&env);
+        for entry in entries.iter() {
+            let (attendee, sig) = entry.unwrap();
+            env.crypto().ed25519_verify(&ed25519_key(&attendee), &message, &sig);
+
+            let stored_att = check_can_attend(&env, &attendee);
+            indexes.push_back(record_attendance(&env, attendee, stored_att, None));
+        }
+        indexes
+    }
+
+    pub fn remaining(env: Env) -> u32 {
+        remaining_unchecked(&env)
+    }
+
+    // Record attendance by the registration number from a printed list
+    pub fn attend_idx(
+        env: Env,
+        reg_index: u32
+    ) -> u32 {
+        if !env.storage().has(DataKey::RegId(reg_index))
+        {
+            panic!("attendee did not register")
+        }
+        let attendee: Identifier = env.storage().get_unchecked(DataKey::RegId(reg_index)).unwrap();
+
+        // The number of someone who left and registered again is stale
+        if !has_attendee(&env, &attendee) || read_attendee(&env, &attendee).reg_index != reg_index
+        {
+            panic!("attendee did not register")
+        }
+        Self::attend(env, attendee, None)
+    }
+
+    // An attendee who was present but not marked (e.g. a missed scan) can contest it before
+    // finalization. Distribution can't start until the dispute is resolved.
+    pub fn dispute(
+        env: Env,
+        attendee: Identifier,
+        evidence_hash: BytesN<32>
+    ) {
+        check_attendee_auth(&env, &attendee);
+        check_not_finalized(&env);
+
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        let mut stored_att = read_attendee(&env, &attendee);
+        if stored_att.attended()
+        {
+            panic!("attendance already recorded")
+        }
+
+        if stored_att.disputed()
+        {
+            panic!("dispute already open")
+        }
+
+        if stored_att.due > 0
+        {
+            panic!("deposit not complete")
+        }
+
+        stored_att.set_flag(FLAG_DISPUTED);
+        stored_att.evidence = Some(evidence_hash);
+        write_attendee(&env, &attendee, stored_att);
+        journal::set(&env, DataKey::Disputes, get_open_disputes(&env) + 1);
+    }
+
+    // Accept the dispute, recording attendance with the submitted evidence, or reject it,
+    // leaving the deposit forfeited
+    pub fn resolve(
+        env: Env,
+        attendee: Identifier,
+        accept: bool
+    ) {
+        check_arbiter(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("resolve"), Some(attendee.clone()));
+
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        let mut stored_att = read_attendee(&env, &attendee);
+        if !stored_att.disputed()
+        {
+            panic!("no open dispute")
+        }
+
+        if accept {
+            let evidence = stored_att.evidence.clone();
+            record_attendance(&env, attendee, stored_att, evidence);
+        } else {
+            stored_att.clear_flag(FLAG_DISPUTED);
+            write_attendee(&env, &attendee, stored_att);
+            close_dispute(&env);
+        }
+    }
+
+    pub fn is_reg(
+        env: Env,
+        attendee: Identifier
+    ) -> bool {
+        has_attendee(&env, &attendee)
+    }
+
+    pub fn attendee(
+        env: Env,
+        attendee: Identifier
+    ) -> Attendee {
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+        read_attendee(&env, &attendee)
+    }
+
+    // Attendees with withdrawal indexes `cursor..cursor + limit` who haven't been paid yet
+    pub fn claims(
+        env: Env,
+        cursor: u32,
+        limit: u32
+    ) -> Vec<Identifier> {
+        check_range(limit, 0);
+
+        let withdrawals = get_withdrawals(&env);
+        let mut pending = Vec::new(&env);
+        for id in cursor..cursor.saturating_add(limit) {
+            if id >= withdrawals.len()
+            {
+                break;
+            }
+
+            let att: Identifier = withdrawals.get_unchecked(id).unwrap();
+            if has_attendee(&env, &att) && !read_attendee(&env, &att).refunded()
+            {
+                pending.push_back(att);
+            }
+        }
+        pending
+    }
+
+    // Compute what a batch of attendees would receive from `withdraw` without transferring anything
+    pub fn simulate(
+        env: Env,
+        high: u32,
+        low: u32,
+    ) -> Vec<(Identifier, i128)> {
+        check_range(high, low);
+
+        let withdrawals = get_withdrawals(&env);
+        let mut payouts = Vec::new(&env);
+        for id in low..high {
+            if id >= withdrawals.len()
+            {
+                break;
+            }
+
+            let att : Identifier = withdrawals.get_unchecked(id).unwrap();
+            // Purged once settled
+            if !has_attendee(&env, &att)
+            {
+                continue;
+            }
+            let att_struct : Attendee = read_attendee(&env, &att);
+
+            if !att_struct.refunded()
+            {
+                let amount = payout_amount(&env, id, &att_struct);
+                payouts.push_back((att, amount));
+            }
+        }
+        payouts
+    }
+
+    // Freeze deposits and attendance and fix how the pot is divided
+    pub fn finalize(
+        env: Env,
+        mode: DistributionMode
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("finalize"), None);
+        check_not_finalized(&env);
+        if mode == DistributionMode::Refund
+        {
+            panic!("use cancel to refund everyone")
+        }
+        write_finalized(&env, mode);
+    }
+
+    // Call the event off unless `minimum` people registered by `deadline`. Past it, the
+    // event is cancelled and anyone can run `refund`.
+    pub fn set_minreg(
+        env: Env,
+        minimum: u32,
+        deadline: u64
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("min_regs"), None);
+        check_not_finalized(&env);
+
+        if deadline <= env.ledger().timestamp()
+        {
+            panic!("Invalid deadline")
+        }
+        journal::set(&env, DataKey::MinRegs, (minimum, deadline));
+    }
+
+    // Require `percent` of registrants to attend. Finalizing short of it cancels the event instead.
+    pub fn set_quorum(
+        env: Env,
+        percent: u32
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("quorum"), None);
+        check_not_finalized(&env);
+
+        if percent > 100
+        {
+            panic!("Invalid quorum")
+        }
+        journal::set(&env, DataKey::Quorum, percent);
+    }
+
+    // Call off the event, refunding every registrant in full through `refund`
+    pub fn cancel(env: Env) {
+        check_arbiter(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("cancel"), None);
+        check_unguarded(&env);
+        check_not_finalized(&env);
+        write_finalized(&env, DistributionMode::Refund);
+    }
+
+    // Return the fees of the registrants with registration indexes `low..high` once the
+    // event is cancelled. Attendees can be paid through either this or `withdraw`.
+    // Anyone can run it once the minimum registrations were missed.
+    pub fn refund(
+        env: Env,
+        high: u32,
+        low: u32
+    ) -> BatchResult {
+        if !min_registrations_missed(&env)
+        {
+            check_operator(&env, &env.invoker().into());
+        }
+        log_admin_action(&env, symbol!("refund"), None);
+        check_range(high, low);
+
+        if min_registrations_missed(&env)
+        {
+            ensure_finalized(&env);
+        } else {
+            ensure_finalized_by(&env, &env.invoker().into());
+        }
+        if get_mode(&env) != DistributionMode::Refund
+        {
+            panic!("event not cancelled")
+        }
+
+        let token = get_token(&env);
+        let total: u32 = env.storage().get_unchecked(DataKey::NextReg).unwrap();
+        let mut result = BatchResult { paid: 0, skipped: 0, total: 0, remaining: 0 };
+        for index in low..high {
+            if index >= total
+            {
+                break;
+            }
+
+            if !env.storage().has(DataKey::RegId(index))
+            {
+                continue;
+            }
+            let att: Identifier = env.storage().get_unchecked(DataKey::RegId(index)).unwrap();
+            if !has_attendee(&env, &att)
+            {
+                continue;
+            }
+            let mut att_struct = read_attendee(&env, &att);
+            if att_struct.reg_index != index || att_struct.refunded() || att_struct.comped() || att_struct.frozen()
+            {
+                result.skipped += 1;
+                continue;
+            }
+
+            if att_struct.attended() {
+                // Refund mode pays the fee alone, so the withdrawal index doesn't matter
+                result.total += pay_out(&env, &token, NO_WITHDRAWAL_INDEX, &att, att_struct);
+            } else {
+                pay_back(&env, &token, &att, &att_struct, att_struct.fee, symbol!("refund"));
+                env.events().publish(EVT_V1_REFUND, TransferEvent { to: att.clone(), amount: att_struct.fee });
+                result.total += att_struct.fee;
+
+                let paid_out: i128 = env.storage().get_unchecked(DataKey::PaidOut).unwrap();
+                journal::set(&env, DataKey::PaidOut, paid_out + att_struct.fee);
+                let refunds: u32 = env.storage().get_unchecked(DataKey::Refunds).unwrap();
+                journal::set(&env, DataKey::Refunds, refunds + 1);
+                att_struct.set_flag(FLAG_REFUNDED);
+                write_attendee(&env, &att, att_struct);
+            }
+            result.paid += 1;
+        }
+        let registered: u32 = env.storage().get_unchecked(DataKey::Registered).unwrap();
+        let comped: u32 = env.storage().get_unchecked(DataKey::Comped).unwrap();
+        let refunds: u32 = env.storage().get_unchecked(DataKey::Refunds).unwrap();
+        result.remaining = registered - comped - refunds;
+        result
+    }
+
+    // Distribute the money to a batch of attendees. Finalizes in kind if `finalize` wasn't called.
+    pub fn withdraw(
+        env: Env,
+        high: u32,
+        low: u32,
+    ) -> BatchResult {
+        check_operator(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("withdraw"), None);
+
+        check_range(high, low);
+        check_not_vested(&env);
+        check_claims_open(&env);
+
+        ensure_finalized_by(&env, &env.invoker().into());
+
+        let token = get_token(&env);
+        let withdrawals = get_withdrawals(&env);
+        
+        let mut result = BatchResult { paid: 0, skipped: 0, total: 0, remaining: 0 };
+        for id in low..high {
+            if id >= withdrawals.len()
+            {
+                break;
+            }
+
+            let att : Identifier = withdrawals.get_unchecked(id).unwrap();
+            // Purged once settled
+            if !has_attendee(&env, &att)
+            {
+                result.skipped += 1;
+                continue;
+            }
+            let att_struct : Attendee = read_attendee(&env, &att);
+
+            if att_struct.payout_failed()
+            {
+                result.skipped += 1;
+                continue;
+            }
+
+            if !att_struct.refunded() && !att_struct.frozen() && !feedback_pending(&env, &att_struct)
+            {
+                // A payout the token refuses (e.g. a frozen recipient) must not block the rest.
+                // When only the payer's part of a split payout goes through, it is kept in
+                // `claimed` so retrying only sends the rest.
+                let amount = payout_amount(&env, id, &att_struct);
+                let mut sent = 0;
+                let mut refused = false;
+                for (to, part) in unpaid_parts(&att, &att_struct, amount) {
+                    if !try_transfer_from_contract_to_account(&env, &token, &to, &part, symbol!("payout"))
+                    {
+                        refused = true;
+                        break;
+                    }
+                    sent += part;
+                }
+
+                if !refused
+                {
+                    env.events().publish(EVT_V1_PAYOUT, TransferEvent { to: att.clone(), amount });
+                    settle(&env, id, &att, att_struct, amount);
+                    result.total += amount;
+                    result.paid += 1;
+                }
+                else
+                {
+                    let mut failed = att_struct;
+                    failed.claimed += sent;
+                    failed.set_flag(FLAG_PAY_FAILED);
+                    write_attendee(&env, &att, failed);
+                    env.events().publish((symbol!("pay_fail"),), att);
+                    result.skipped += 1;
+                }
+            }
+            else
+            {
+                result.skipped += 1;
+            }
+        }
+        // Once the event is cancelled, refunds also count no-shows
+        let refunds: u32 = env.storage().get_unchecked(DataKey::Refunds).unwrap();
+        result.remaining = withdrawals.len().saturating_sub(refunds);
+        result
+    }
+
+    // Withdrawal index ranges `(low, high)` paid so far. Retrying a batch over them pays nothing
+    // again, the indexes are counted as skipped.
+    pub fn processed(env: Env) -> Vec<(u32, u32)> {
+        get_processed(&env)
+    }
+
+    // Withdrawal indexes in use as `(low, high)`, high exclusive. The payout order may reorder
+    // them at finalization, after which they no longer change.
+    pub fn idx_range(env: Env) -> (u32, u32) {
+        (0, get_withdrawals(&env).len())
+    }
+
+    // Attendee stored at withdrawal index `index`, or `None` past the end of the range
+    pub fn id_at(
+        env: Env,
+        index: u32
+    ) -> Option<Identifier> {
+        match get_withdrawals(&env).get(index) {
+            Some(id) => Some(id.unwrap()),
+            None => None
+        }
+    }
+
+    // Settle a single attendee out of band. Returns the amount paid.
+    pub fn payout_one(
+        env: Env,
+        attendee: Identifier
+    ) -> i128 {
+        check_operator(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("payout"), Some(attendee.clone()));
+        check_not_vested(&env);
+        // Payouts reserved while frozen stay payable after the claim window
+        if !env.storage().has(DataKey::Reserved(attendee.clone()))
+        {
+            check_claims_open(&env);
+        }
+
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        let att_struct = read_attendee(&env, &attendee);
+        if !att_struct.attended()
+        {
+            panic!("attendee did not attend")
+        }
+
+        if att_struct.refunded()
+        {
+            panic!("attendee already refunded")
+        }
+        check_not_frozen(&att_struct);
+
+        if feedback_pending(&env, &att_struct)
+        {
+            panic!("feedback required")
+        }
+
+        ensure_finalized_by(&env, &env.invoker().into());
+
+        let id = withdrawal_index(&env, &attendee, &att_struct);
+        pay_out(&env, &get_token(&env), id, &attendee, att_struct)
+    }
+
+    // Give the attendee's payout, or its vested part, to an approved charity instead. A payer's
+    // share of a split deposit still goes back to the payer. Returns the amount donated.
+    pub fn donate(
+        env: Env,
+        attendee: Identifier,
+        charity: Identifier
+    ) -> i128 {
+        if !env.storage().has(DataKey::Charity(charity.clone()))
+        {
+            panic!("charity not approved")
+        }
+
+        let att_struct = check_own_payout(&env, &attendee);
+        let id = withdrawal_index(&env, &attendee, &att_struct);
+        let total = payout_amount(&env, id, &att_struct);
+        let vested = vested_amount(&env, total);
+        let amount = vested - att_struct.claimed;
+        if amount == 0
+        {
+            panic!("nothing to claim")
+        }
+        let token = get_token(&env);
+        let [(payer, payer_amount), (_, donated)] = split_amount(&attendee, &att_struct, amount);
+        transfer_from_contract_to_account(&env, &token, &payer, &payer_amount, symbol!("payout"));
+        transfer_from_contract_to_account(&env, &token, &charity, &donated, symbol!("donation"));
+        env.events().publish(
+            EVT_V1_DONATE,
+            DonateEvent { attendee: attendee.clone(), charity, amount: donated }
+        );
+
+        journal::set(&env, DataKey::Donated, get_donated(&env) + donated);
+        settle_vested(&env, id, &attendee, att_struct, amount, vested == total);
+        donated
+    }
+
+    // Receive the payout converted into `buy` through the swap contract, getting at least
+    // `min_out`. A payer's share of a split deposit is paid back unconverted.
+    // Returns the amount of `buy` received.
+    pub fn claim_swap(
+        env: Env,
+        attendee: Identifier,
+        buy: BytesN<32>,
+        min_out: i128
+    ) -> i128 {
+        if !env.storage().has(DataKey::Swap)
+        {
+            panic!("swap not set")
+        }
+
+        let att_struct = check_own_payout(&env, &attendee);
+        let id = withdrawal_index(&env, &attendee, &att_struct);
+        let total = payout_amount(&env, id, &att_struct);
+        let vested = vested_amount(&env, total);
+        let amount = vested - att_struct.claimed;
+        if amount == 0
+        {
+            panic!("nothing to claim")
+        }
+        let token = get_token(&env);
+        let [(payer, payer_amount), (_, own)] = split_amount(&attendee, &att_struct, amount);
+        transfer_from_contract_to_account(&env, &token, &payer, &payer_amount, symbol!("payout"));
+
+        let swap: BytesN<32> = env.storage().get_unchecked(DataKey::Swap).unwrap();
+        let client = token::Client::new(&env, &token);
+        client.incr_allow(&Signature::Invoker, &0, &Identifier::Contract(swap.clone()), &own);
+        let bought = swap::Client::new(&env, &swap).swap(
+            &get_contract_id(&env), &attendee, &token, &buy, &own, &min_out
+        );
+        journal::set(&env, DataKey::Held, get_held(&env) - own);
+        ledger::record(&env, symbol!("swap"), attendee.clone(), -own);
+        env.events().publish(EVT_V1_PAYOUT, TransferEvent { to: attendee.clone(), amount: own });
+
+        settle_vested(&env, id, &attendee, att_struct, amount, vested == total);
+        bought
+    }
+
+    // Collect the vested part of the attendee's payout. Returns the amount transferred.
+    pub fn claim(
+        env: Env,
+        attendee: Identifier
+    ) -> i128 {
+        check_attendee_auth(&env, &attendee);
+
+        let duration = get_vesting(&env);
+        if duration == 0 || !is_finalized(&env)
+        {
+            panic!("nothing to claim")
+        }
+        check_claims_open(&env);
+
+        if !has_attendee(&env, &attendee) {
+            panic!("attendee did not register");
+        }
+
+        let mut att_struct = read_attendee(&env, &attendee);
+        if !att_struct.attended() || att_struct.refunded()
+        {
+            panic!("nothing to claim")
+        }
+        check_not_frozen(&att_struct);
+
+        if feedback_pending(&env, &att_struct)
+        {
+            panic!("feedback required")
+        }
+
+        let id = withdrawal_index(&env, &attendee, &att_struct);
+        let total = payout_amount(&env, id, &att_struct);
+
+        let vested = vested_amount(&env, total);
+        let amount = vested - att_struct.claimed;
+        if amount == 0
+        {
+            panic!("nothing to claim")
+        }
+
+        att_struct.claimed = vested;
+        record_payout(&env, &attendee, &mut att_struct, amount);
+        if vested == total
+        {
+            att_struct.set_flag(FLAG_REFUNDED);
+            let refunds: u32 = env.storage().get_unchecked(DataKey::Refunds).unwrap();
+            journal::set(&env, DataKey::Refunds, refunds + 1);
+        }
+        write_attendee(&env, &attendee, att_struct.clone());
+
+        let paid_out: i128 = env.storage().get_unchecked(DataKey::PaidOut).unwrap();
+        journal::set(&env, DataKey::PaidOut, paid_out + amount);
+
+        pay_back(&env, &get_token(&env), &attendee, &att_struct, amount, symbol!("payout"));
+        env.events().publish(EVT_V1_PAYOUT, TransferEvent { to: attendee, amount });
+        amount
+    }
+
+    // Send what is left of the pot after every attendee was paid to the beneficiary under the `Sweep` policy
+    pub fn sweep_rem(env: Env) -> i128 {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("sweep"), None);
+        check_unguarded(&env);
+        sweep(&env)
+    }
+
+    // Move attendee records written before keys were namespaced. Returns how many were migrated.
+    pub fn mig_atts(
+        env: Env,
+        ids: Vec<Identifier>
+    ) -> u32 {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("migrate"), None);
+
+        if ids.len() > MAX_BATCH
+        {
+            panic!("Invalid range")
+        }
+
+        let mut migrated = 0;
+        for id in ids.iter() {
+            if migrate_attendee(&env, &id.unwrap()) {
+                migrated += 1;
+            }
+        }
+        migrated
+    }
+
+    // Rewrite up to `max` legacy withdrawal keys, starting at `start`, into the v2 layout.
+    // Batches must be run in order. Returns how many entries were migrated.
+    pub fn mig_v1_v2(
+        env: Env,
+        start: u32,
+        max: u32
+    ) -> u32 {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("migrate"), None);
+
+        if max > MAX_BATCH
+        {
+            panic!("Invalid range")
+        }
+
+        if !env.storage().has(DataKey::Count)
+        {
+            panic!("nothing to migrate")
+        }
+
+        // Keys introduced by v2 are missing on legacy deployments
+        if !env.storage().has(DataKey::Withdraws)
+        {
+            journal::set(&env, DataKey::Withdraws, Vec::<Identifier>::new(&env));
+            journal::set(&env, DataKey::Registered, 0 as u32);
+            journal::set(&env, DataKey::NextReg, 0 as u32);
+            journal::set(&env, DataKey::Remainder, RemainderPolicy::Retain);
+            journal::set(&env, DataKey::AttFees, 0 as i128);
+            journal::set(&env, DataKey::PaidOut, 0 as i128);
+            journal::set(&env, DataKey::Refunds, 0 as u32);
+            journal::set(&env, DataKey::Tiers, Vec::<u32>::new(&env));
+
+            // Everything a legacy deployment holds belongs to its attendees
+            let client = token::Client::new(&env, &get_token(&env));
+            journal::set(&env, DataKey::Held, client.balance(&get_contract_id(&env)));
+            journal::set(&env, DataKey::ProposalN, 0 as u32);
+            journal::set(&env, DataKey::Pending, Vec::<u32>::new(&env));
+            journal::set(&env, DataKey::Comped, 0 as u32);
+            journal::set(&env, DataKey::CompAtt, 0 as u32);
+        }
+
+        let legacy_count: u32 = env.storage().get_unchecked(DataKey::Count).unwrap();
+        let mut withdrawals = get_withdrawals(&env);
+        if start != withdrawals.len()
+        {
+            panic!("migration out of order")
+        }
+
+        let mut end = start + max;
+        if end > legacy_count
+        {
+            end = legacy_count;
+        }
+
+        for id in start..end {
+            let att : Identifier = env.storage().get_unchecked(id).unwrap();
+            migrate_attendee(&env, &att);
+            withdrawals.push_back(att);
+            journal::remove(&env, id);
+        }
+        journal::set(&env, DataKey::Withdraws, withdrawals);
+
+        if end == legacy_count
+        {
+            journal::remove(&env, DataKey::Count);
+            journal::set(&env, DataKey::StorageVer, STORAGE_VERSION);
+        }
+        end - start
+    }
+
+    // Require `threshold` of `approvers` to agree on sweeping and on changing the
+    // beneficiary or remainder policy. Can only be configured once.
+    pub fn set_appr(
+        env: Env,
+        approvers: Vec<Identifier>,
+        threshold: u32
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("approvers"), None);
+
+        if has_approvers(&env)
+        {
+            panic!("approvers already set")
+        }
+
+        if threshold == 0 || threshold > approvers.len()
+        {
+            panic!("invalid threshold")
+        }
+
+        journal::set(&env, DataKey::Approvers, approvers);
+        journal::set(&env, DataKey::Threshold, threshold);
+    }
+
+    // Delay in seconds between proposing a sensitive action and it taking effect. Can only be increased.
+    pub fn set_tlock(
+        env: Env,
+        delay: u64
+    ) {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("timelock"), None);
+
+        if delay < get_timelock(&env)
+        {
+            panic!("timelock can only be increased")
+        }
+        journal::set(&env, DataKey::Timelock, delay);
+    }
+
+    // Propose a sensitive action, counting as the proposer's approval. Returns the action id.
+    pub fn propose(
+        env: Env,
+        action: AdminAction
+    ) -> u32 {
+        let invoker: Identifier = env.invoker().into();
+        check_approver(&env, &invoker);
+
+        let action_id: u32 = env.storage().get_unchecked(DataKey::ProposalN).unwrap();
+        journal::set(&env, DataKey::ProposalN, action_id + 1);
+
+        let mut pending: Vec<u32> = env.storage().get_unchecked(DataKey::Pending).unwrap();
+        pending.push_back(action_id);
+        journal::set(&env, DataKey::Pending, pending);
+
+        let proposal = Proposal {
+            action: action.clone(),
+            approvals: Vec::new(&env),
+            ready_at: env.ledger().timestamp() + get_timelock(&env),
+            executed: false
+        };
+        journal::set(&env, DataKey::Proposal(action_id), proposal.clone());
+
+        // Let attendees know in time to react
+        env.events().publish((symbol!("proposed"), action_id), (action, proposal.ready_at));
+
+        Self::approve(env.clone(), action_id);
+        action_id
+    }
+
+    // Approve a proposed action; it is executed as soon as the threshold is reached and the timelock passed
+    pub fn approve(
+        env: Env,
+        action_id: u32
+    ) {
+        let invoker: Identifier = env.invoker().into();
+        check_approver(&env, &invoker);
+
+        let mut proposal = read_proposal(&env, action_id);
+        if proposal.executed
+        {
+            panic!("action already executed")
+        }
+
+        if proposal.approvals.contains(&invoker)
+        {
+            panic!("action already approved")
+        }
+        proposal.approvals.push_back(invoker);
+
+        try_execute(&env, action_id, &mut proposal);
+        journal::set(&env, DataKey::Proposal(action_id), proposal);
+    }
+
+    // Execute an approved action once its timelock has passed
+    pub fn execute(
+        env: Env,
+        action_id: u32
+    ) {
+        let invoker: Identifier = env.invoker().into();
+        check_approver(&env, &invoker);
+
+        let mut proposal = read_proposal(&env, action_id);
+        if proposal.executed
+        {
+            panic!("action already executed")
+        }
+
+        if !try_execute(&env, action_id, &mut proposal)
+        {
+            panic!("action not executable yet")
+        }
+        journal::set(&env, DataKey::Proposal(action_id), proposal);
+    }
+
+    // Proposals waiting for approvals or for their timelock, as (action id, proposal) pairs
+    pub fn pending(env: Env) -> Vec<(u32, Proposal)> {
+        let pending: Vec<u32> = env.storage().get_unchecked(DataKey::Pending).unwrap();
+        let mut proposals = Vec::new(&env);
+        for action_id in pending.iter() {
+            let action_id = action_id.unwrap();
+            proposals.push_back((action_id, read_proposal(&env, action_id)));
+        }
+        proposals
+    }
+
+    pub fn proposal(
+        env: Env,
+        action_id: u32
+    ) -> Proposal {
+        read_proposal(&env, action_id)
+    }
+
+    // Token movements with sequence numbers `low..high`, oldest first
+    pub fn ledger(
+        env: Env,
+        high: u32,
+        low: u32
+    ) -> Vec<LedgerEntry> {
+        check_range(high, low);
+        ledger::entries(&env, high, low)
+    }
+
+    // Audit log entries with sequence numbers in [low, high). Entries that were
+    // overwritten by newer ones are skipped.
+    pub fn audit_log(
+        env: Env,
+        high: u32,
+        low: u32
+    ) -> Vec<AuditEntry> {
+        check_range(high, low);
+
+        let count = get_audit_count(&env);
+        let mut entries = Vec::new(&env);
+        for seq in low..high {
+            if seq >= count
+            {
+                break;
+            }
+            if seq + AUDIT_LOG_SIZE < count
+            {
+                continue;
+            }
+            entries.push_back(env.storage().get_unchecked(DataKey::AuditLog(seq % AUDIT_LOG_SIZE)).unwrap());
+        }
+        entries
+    }
+
+    // Delete the records of paid out attendees among registration indexes `start..start + max`
+    // to reclaim rent. Counters and totals are kept, so stats don't change. Returns how many were purged.
+    pub fn purge(
+        env: Env,
+        start: u32,
+        max: u32
+    ) -> u32 {
+        check_admin(&env, &env.invoker().into());
+        log_admin_action(&env, symbol!("purge"), None);
+        check_range(max, 0);
+
+        // Disputes can only be opened before finalization, so settled records can't be contested anymore
+        if !is_finalized(&env)
+        {
+            panic!("distribution not started")
+        }
+
+        let mut purged = 0;
+        for index in start..start.saturating_add(max) {
+            if !env.storage().has(DataKey::RegId(index))
+            {
+                continue;
+            }
+            let id: Identifier = env.storage().get_unchecked(DataKey::RegId(index)).unwrap();
+            if !has_attendee(&env, &id)
+            {
+                continue;
+            }
+            let att = read_attendee(&env, &id);
+            if att.reg_index == index && att.refunded() && !att.frozen()
+            {
+                remove_registration(&env, index);
+                purged += 1;
+            }
+        }
+        journal::set(&env, DataKey::Purged, get_purged(&env) + purged);
+        purged
+    }
+
+    // Delete the settled attendee's record and any link from the index to their identifier,
+    // leaving an anonymous `Tombstone` so totals still add up. Ledger and audit log entries
+    // and events already published keep the identifier.
+    pub fn erase_me(
+        env: Env,
+        attendee: Identifier
+    ) {
+        check_attendee_auth(&env, &attendee);
+        // Disputes can only be opened before finalization
+        if !is_finalized(&env)
+        {
+            panic!("distribution not started")
+        }
+        if !has_attendee(&env, &attendee)
+        {
+            panic!("attendee did not register")
+        }
+
+        let att = read_attendee(&env, &attendee);
+        if !att.refunded() || att.frozen()
+        {
+            panic!("not settled")
+        }
+
+        let mut withdrawals = get_withdrawals(&env);
+        if let Some(index) = withdrawals.first_index_of(&attendee) {
+            withdrawals.set(index, erased_id(&env));
+            journal::set(&env, DataKey::Withdraws, withdrawals);
+        }
+        let tombstone = Tombstone { fee: att.fee, flags: att.flags, paid: att.princ_paid + att.bonus_paid };
+        remove_registration(&env, att.reg_index);
+        if env.storage().has(DataKey::SubAcct(attendee.clone()))
+        {
+            journal::remove(&env, DataKey::SubAcct(attendee.clone()));
+        }
+        journal::set(&env, DataKey::Tombstone(att.reg_index), tombstone);
+        journal::set(&env, DataKey::Purged, get_purged(&env) + 1);
+    }
+
+    pub fn tombstone(
+        env: Env,
+        reg_index: u32
+    ) -> Option<Tombstone> {
+        if !env.storage().has(DataKey::Tombstone(reg_index)) {
+            return None;
+        }
+        Some(env.storage().get_unchecked(DataKey::Tombstone(reg_index)).unwrap())
+    }
+
+    // Commit to the final attendee state, adding up to `max` registrations per call. Leaves are
+    // the sha256 of `(identifier, fee, attended, payout)` for every record, in registration
+    // order, so it must run before `purge`. Returns the root once all are in.
+    pub fn commit(
+        env: Env,
+        max: u32
+    ) -> Option<BytesN<32>> {
+        check_range(max, 0);
+        if !is_finalized(&env)
+        {
+            panic!("distribution not started")
+        }
+        if env.storage().has(DataKey::MerkleRoot)
+        {
+            return Some(env.storage().get_unchecked(DataKey::MerkleRoot).unwrap());
+        }
+
+        let total: u32 = env.storage().get_unchecked(DataKey::NextReg).unwrap();
+        let mut cursor = 0;
+        if env.storage().has(DataKey::MerkleCur) {
+            cursor = env.storage().get_unchecked(DataKey::MerkleCur).unwrap();
+        }
+        let end = total.min(cursor + max);
+        while cursor < end {
+            if let Some(leaf) = state_leaf(&env, cursor) {
+                merkle::append(&env, leaf);
+            }
+            cursor += 1;
+        }
+        journal::set(&env, DataKey::MerkleCur, cursor);
+
+        if cursor < total
+        {
+            return None;
+        }
+        let root = merkle::root(&env);
+        journal::set(&env, DataKey::MerkleRoot, root.clone());
+        Some(root)
+    }
+
+    pub fn state_root(env: Env) -> Option<BytesN<32>> {
+        if !env.storage().has(DataKey::MerkleRoot) {
+            return None;
+        }
+        Some(env.storage().get_unchecked(DataKey::MerkleRoot).unwrap())
+    }
+
+    // Once the event is settled, delete up to `max` storage entries per call so nothing is left
+    // paying rent. Only the admin, the mode and the closed marker are kept, which keeps the
+    // contract from being initialized or registered with again. Returns the entries left.
+    pub fn close(
+        env: Env,
+        max: u32
+    ) -> u32 {
+        check_admin(&env, &env.invoker().into());
+        check_range(max, 0);
+
+        if env.storage().has(DataKey::Closed)
+        {
+            panic!("contract closed")
+        }
+
+        if !env.storage().has(DataKey::CloseCur) {
+            let refunds: u32 = env.storage().get_unchecked(DataKey::Refunds).unwrap();
+            if !is_finalized(&env) || refunds < get_count(&env)
+            {
+                panic!("distribution not complete")
+            }
+            if get_held(&env) > 0
+            {
+                panic!("balance not swept")
+            }
+            // Logged once, before the audit log slots are counted
+            log_admin_action(&env, symbol!("close"), None);
+            journal::set(&env, DataKey::CloseCur, 0 as u32);
+        }
+
+        // Entries are visited as registrations, then audit log slots, proposals, ledger entries
+        // and tracked keys
+        let registrations: u32 = env.storage().get_unchecked(DataKey::NextReg).unwrap();
+        let mut audit_slots = get_audit_count(&env);
+        if audit_slots > AUDIT_LOG_SIZE {
+            audit_slots = AUDIT_LOG_SIZE;
+        }
+        let mut proposals = 0;
+        if env.storage().has(DataKey::ProposalN) {
+            proposals = env.storage().get_unchecked(DataKey::ProposalN).unwrap();
+        }
+        let movements = ledger::count(&env);
+        let tracked = get_tracked_count(&env);
+        let total = registrations + audit_slots + proposals + movements + tracked;
+
+        let mut cursor: u32 = env.storage().get_unchecked(DataKey::CloseCur).unwrap();
+        let end = cursor + max;
+        while cursor < total && cursor < end {
+            if cursor < registrations {
+                remove_registration(&env, cursor);
+            } else if cursor < registrations + audit_slots {
+                journal::remove(&env, DataKey::AuditLog(cursor - registrations));
+            } else if cursor < registrations + audit_slots + proposals {
+                journal::remove(&env, DataKey::Proposal(cursor - registrations - audit_slots));
+            } else if cursor < registrations + audit_slots + proposals + movements {
+                journal::remove(&env, DataKey::Ledger(cursor - registrations - audit_slots - proposals));
+            } else {
+                let index = cursor - registrations - audit_slots - proposals - movements;
+                let key: DataKey = env.storage().get_unchecked(DataKey::Tracked(index)).unwrap();
+                if env.storage().has(key.clone()) {
+                    journal::remove(&env, key);
+                }
+                journal::remove(&env, DataKey::Tracked(index));
+            }
+            cursor += 1;
+        }
+        journal::set(&env, DataKey::CloseCur, cursor);
+
+        if cursor < total {
+            return total - cursor;
+        }
+        remove_config(&env);
+        journal::set(&env, DataKey::Closed, true);
+        0
+    }
+
+    pub fn max_regs(_env: Env) -> u32 {
+        MAX_REGISTRATIONS
+    }
+
+    pub fn version(_env: Env) -> u32 {
+        CONTRACT_VERSION
+    }
+
+    // Optional capabilities this build supports, for integrators and indexers
+    pub fn features(env: Env) -> Vec<Symbol> {
+        vec![
+            &env,
+            symbol!("install"),
+            symbol!("evidence"),
+            symbol!("simulate"),
+            symbol!("remainder"),
+            symbol!("allowlist"),
+            symbol!("blocklist"),
+            symbol!("oracle"),
+            symbol!("pro_rata"),
+            symbol!("tiers"),
+            symbol!("prepaid"),
+            symbol!("checkpoint"),
+            symbol!("audit_log"),
+            symbol!("multisig"),
+            symbol!("timelock"),
+            symbol!("exit"),
+            symbol!("vesting"),
+            symbol!("sponsor"),
+            symbol!("raffle"),
+            symbol!("bonus_cap"),
+            symbol!("feedback"),
+            symbol!("teams"),
+            symbol!("comps"),
+            symbol!("surcharge"),
+            symbol!("batch_res"),
+            symbol!("split"),
+            symbol!("export"),
+            symbol!("events_v1"),
+            symbol!("tickets"),
+            symbol!("disputes"),
+            symbol!("arbiter"),
+            symbol!("quorum"),
+            symbol!("yield"),
+            symbol!("freeze"),
+            symbol!("fee_split"),
+            symbol!("close"),
+            symbol!("unpaid"),
+            symbol!("order"),
+            symbol!("reregister"),
+            symbol!("reg_cap"),
+            symbol!("top_up"),
+            symbol!("tier_chg"),
+            symbol!("purge"),
+            symbol!("init_cfg"),
+            symbol!("status"),
+            symbol!("attend_idx"),
+            symbol!("ledger"),
+            symbol!("nonce"),
+            symbol!("overbook"),
+            symbol!("resume"),
+            symbol!("auto_key"),
+            symbol!("donate"),
+            symbol!("swap"),
+            symbol!("merkle"),
+            symbol!("sig_batch"),
+            symbol!("rsvp"),
+            symbol!("skip_fail"),
+            symbol!("bounds"),
+            symbol!("receipts"),
+            symbol!("door_list"),
+            symbol!("phases"),
+            symbol!("contact"),
+            symbol!("transfer"),
+            symbol!("decimals"),
+            symbol!("grace"),
+            symbol!("by_time"),
+            symbol!("index_view"),
+            symbol!("payout_v2"),
+            symbol!("sub_ids"),
+            symbol!("attestor"),
+            symbol!("parent"),
+            symbol!("erase"),
+            symbol!("price_step"),
+            symbol!("min_regs"),
+            symbol!("referrals"),
+            symbol!("validate"),
+            symbol!("seat_limit"),
+            symbol!("reserve")
+        ]
+    }
+
+    // Snapshot of the configuration plus the attendees registered with indexes `cursor..cursor + limit`
+    pub fn export(
+        env: Env,
+        cursor: u32,
+        limit: u32
+    ) -> StateChunk {
+        check_range(limit, 0);
+
+        let total: u32 = env.storage().get_unchecked(DataKey::NextReg).unwrap();
+        let mut attendees = Vec::new(&env);
+        let mut next_cursor = cursor;
+        while next_cursor < total && next_cursor < cursor + limit {
+            if env.storage().has(DataKey::RegId(next_cursor)) {
+                let id: Identifier = env.storage().get_unchecked(DataKey::RegId(next_cursor)).unwrap();
+                // Skip records of attendees who left, and of those who registered again since
+                if has_attendee(&env, &id) {
+                    let att = read_attendee(&env, &id);
+                    if att.reg_index == next_cursor {
+                        attendees.push_back((id, att));
+                    }
+                }
+            }
+            next_cursor += 1;
+        }
+
+        let mut mode = None;
+        if is_finalized(&env) {
+            mode = Some(get_mode(&env));
+        }
+
+        StateChunk {
+            stats: Self::stats(env.clone()),
+            token: get_token(&env),
+            mode,
+            attendees,
+            next: next_cursor,
+            total
+        }
+    }
+
+    pub fn status(env: Env) -> StatusReport {
+        let refunds: u32 = env.storage().get_unchecked(DataKey::Refunds).unwrap();
+        let mut mode = None;
+        let mut finalized_at = None;
+        if is_finalized(&env) {
+            mode = Some(get_mode(&env));
+            finalized_at = Some(env.storage().get_unchecked(DataKey::FinalAt).unwrap());
+        }
+
+        let mut installment_deadline = None;
+        if env.storage().has(DataKey::InstDue) {
+            installment_deadline = Some(env.storage().get_unchecked(DataKey::InstDue).unwrap());
+        }
+
+        StatusReport {
+            phase: current_phase(&env),
+            mode,
+            inst_due: installment_deadline,
+            final_at: finalized_at,
+            registered: env.storage().get_unchecked(DataKey::Registered).unwrap(),
+            attended: get_attended(&env),
+            paid: refunds,
+            disputes: get_open_disputes(&env),
+            pool: get_unclaimed(&env),
+            paid_out: env.storage().get_unchecked(DataKey::PaidOut).unwrap(),
+            held: get_held(&env)
+        }
+    }
+
+    pub fn phase(env: Env) -> Phase {
+        current_phase(&env)
+    }
+
+    pub fn stats(env: Env) -> Stats {
+        Stats {
+            price: current_price(&env),
+            registered: env.storage().get_unchecked(DataKey::Registered).unwrap(),
+            attended: get_attended(&env),
+            unclaimed: get_unclaimed(&env),
+            remainder: get_remainder_policy(&env),
+            sponsored: get_sponsor_pool(&env),
+            comped: env.storage().get_unchecked(DataKey::Comped).unwrap(),
+            purged: get_purged(&env),
+            donated: get_donated(&env)
+        }
+    }
+}
+
+fn get_donated(e: &Env) -> i128 {
+    if !e.storage().has(DataKey::Donated) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Donated).unwrap()
+}
+
+fn get_schedule(e: &Env) -> Option<Schedule> {
+    if !e.storage().has(DataKey::Schedule) {
+        return None;
+    }
+    Some(e.storage().get_unchecked(DataKey::Schedule).unwrap())
+}
+
+// Scheduled phases take effect as time passes; the distribution itself is finalized by the
+// first payout after `claim_open` and the grace period
+fn current_phase(e: &Env) -> Phase {
+    let now = e.ledger().timestamp();
+    let schedule = get_schedule(e);
+    if !is_finalized(e) {
+        return match schedule {
+            Some(s) if now >= s.claim_open + get_grace(e) => Phase::Paying,
+            Some(s) if now >= s.reg_end => Phase::CheckIn,
+            _ => Phase::Register
+        };
+    }
+
+    let refunds: u32 = e.storage().get_unchecked(DataKey::Refunds).unwrap();
+    if get_mode(e) == DistributionMode::Refund {
+        Phase::Cancelled
+    } else if refunds >= get_count(e) {
+        Phase::Settled
+    } else if claims_closed(e) {
+        Phase::Closed
+    } else {
+        Phase::Paying
+    }
+}
+
+fn claims_closed(e: &Env) -> bool {
+    match get_schedule(e) {
+        Some(s) => e.ledger().timestamp() >= s.claim_end,
+        None => false
+    }
+}
+
+fn check_claims_open(e: &Env) {
+    if claims_closed(e) {
+        panic!("claim window closed");
+    }
+    // Late check-ins can't be taken once the distribution is finalized
+    if let Some(schedule) = get_schedule(e) {
+        if !is_finalized(e) && e.ledger().timestamp() < schedule.claim_open + get_grace(e) {
+            panic!("grace period running");
+        }
+    }
+}
+
+fn get_purged(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::Purged) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Purged).unwrap()
+}
+
+fn has_beneficiary(e: &Env) -> bool {
+    e.storage().has(DataKey::Benef) || e.storage().has(DataKey::Splits)
+}
+
+fn get_fees_owed(e: &Env) -> i128 {
+    if !e.storage().has(DataKey::FeesOwed) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::FeesOwed).unwrap()
+}
+
+fn sweep(e: &Env) -> i128 {
+    if is_finalized(e) && get_mode(e) == DistributionMode::Refund
+    {
+        return sweep_cancelled(e);
+    }
+
+    if get_remainder_policy(e) != RemainderPolicy::Sweep && !e.storage().has(DataKey::BonusCap)
+    {
+        panic!("remainder policy is not sweep")
+    }
+
+    if e.storage().has(DataKey::Swept)
+    {
+        panic!("remainder already swept")
+    }
+
+    if !has_beneficiary(e)
+    {
+        panic!("beneficiary not set")
+    }
+
+    // Unclaimed payouts are swept too once the claim window has closed
+    let refunds: u32 = e.storage().get_unchecked(DataKey::Refunds).unwrap();
+    if !is_finalized(e) || (refunds < get_count(e) && !claims_closed(e))
+    {
+        panic!("distribution not complete")
+    }
+
+    let paid_out: i128 = e.storage().get_unchecked(DataKey::PaidOut).unwrap();
+    let reserve = reserve_frozen(e);
+    let remainder = get_attended_fees(e) + get_unclaimed(e) + get_sponsor_pool(e) - paid_out - reserve;
+    journal::set(e, DataKey::Swept, true);
+
+    // Co-organizers are paid together through `pay_fees`
+    if e.storage().has(DataKey::Splits)
+    {
+        journal::set(e, DataKey::FeesOwed, get_fees_owed(e) + remainder);
+        return remainder;
+    }
+
+    let beneficiary: Identifier = e.storage().get_unchecked(DataKey::Benef).unwrap();
+    if remainder > 0
+    {
+        let token = get_token(e);
+        transfer_from_contract_to_account(e, &token, &beneficiary, &remainder, symbol!("sweep"));
+    }
+    e.events().publish(EVT_V1_SWEEP, TransferEvent { to: beneficiary, amount: remainder });
+    remainder
+}
+
+// A cancelled event keeps what it owes registrants and sponsors; anything beyond that, such
+// as accrued yield, goes to the beneficiary once every registrant has been refunded
+fn sweep_cancelled(e: &Env) -> i128 {
+    if e.storage().has(DataKey::Swept)
+    {
+        panic!("remainder already swept")
+    }
+
+    if !has_beneficiary(e)
+    {
+        panic!("beneficiary not set")
+    }
+
+    let registered: u32 = e.storage().get_unchecked(DataKey::Registered).unwrap();
+    let comped: u32 = e.storage().get_unchecked(DataKey::Comped).unwrap();
+    let refunds: u32 = e.storage().get_unchecked(DataKey::Refunds).unwrap();
+    if refunds < registered - comped
+    {
+        panic!("distribution not complete")
+    }
+
+    let excess = get_held(e) - get_sponsor_pool(e);
+    journal::set(e, DataKey::Swept, true);
+
+    if e.storage().has(DataKey::Splits)
+    {
+        journal::set(e, DataKey::FeesOwed, get_fees_owed(e) + excess);
+        return excess;
+    }
+
+    let beneficiary: Identifier = e.storage().get_unchecked(DataKey::Benef).unwrap();
+    if excess > 0
+    {
+        transfer_from_contract_to_account(e, &get_token(e), &beneficiary, &excess, symbol!("sweep"));
+    }
+    e.events().publish(EVT_V1_SWEEP, TransferEvent { to: beneficiary, amount: excess });
+    excess
+}
+
+fn unbind_ticket(e: &Env, id: &Identifier) {
+    if !e.storage().has(DataKey::TicketOf(id.clone())) {
+        return;
+    }
+    let ticket_id: BytesN<32> = e.storage().get_unchecked(DataKey::TicketOf(id.clone())).unwrap();
+    journal::remove(e, DataKey::Ticket(ticket_id));
+    journal::remove(e, DataKey::TicketOf(id.clone()));
+}
+
+// The venue has a capacity and that many attendees already checked in
+fn venue_full(env: &Env) -> bool {
+    if !env.storage().has(DataKey::Capacity) {
+        return false;
+    }
+    let capacity: u32 = env.storage().get_unchecked(DataKey::Capacity).unwrap();
+    get_attended(env) >= capacity
+}
+
+// Leaf of `commit` for registration `index`, if that registration is still on record
+fn state_leaf(e: &Env, index: u32) -> Option<BytesN<32>> {
+    if !e.storage().has(DataKey::RegId(index)) {
+        return None;
+    }
+    let id: Identifier = e.storage().get_unchecked(DataKey::RegId(index)).unwrap();
+    if !has_attendee(e, &id) {
+        return None;
+    }
+    let att = read_attendee(e, &id);
+    if att.reg_index != index {
+        return None;
+    }
+
+    let mut payout = 0;
+    if att.late() {
+        // Late arrivals were handed their fee back at check-in and have no withdrawal slot
+        payout = att.fee;
+    } else if att.attended() && !att.comped() {
+        let withdrawal = get_withdrawals(e).first_index_of(&id).unwrap();
+        payout = payout_amount(e, withdrawal, &att);
+    }
+    let leaf = (id, att.fee, att.attended(), payout);
+    Some(e.crypto().sha256(&e.serialize_to_bytes(leaf)))
+}
+
+// Position of an attendee in the payout order; comped attendees have none
+fn withdrawal_index(e: &Env, attendee: &Identifier, att: &Attendee) -> u32 {
+    if att.comped() {
+        fail(Error::Comped);
+    }
+    get_withdrawals(e).first_index_of(attendee).unwrap()
+}
+
+// Identifier standing for the user `sub_id` of `owner`. It is a hash, so no key can sign for it.
+fn sub_identifier(e: &Env, owner: &Identifier, sub_id: u64) -> Identifier {
+    Identifier::Ed25519(e.crypto().sha256(&e.serialize_to_bytes((owner.clone(), sub_id))))
+}
+
+fn get_sub_account(e: &Env, attendee: &Identifier) -> Option<SubAccount> {
+    if !e.storage().has(DataKey::SubAcct(attendee.clone())) {
+        return None;
+    }
+    Some(e.storage().get_unchecked(DataKey::SubAcct(attendee.clone())).unwrap())
+}
+
+// The attendee acts for themselves, the owner of a shared account for its sub-ids
+fn is_attendee_auth(e: &Env, invoker: &Identifier, attendee: &Identifier) -> bool {
+    if *invoker == *attendee {
+        return true;
+    }
+    match get_sub_account(e, attendee) {
+        Some(sub) => sub.owner == *invoker,
+        None => false,
+    }
+}
+
+fn check_attendee_auth(e: &Env, attendee: &Identifier) {
+    let invoker: Identifier = e.invoker().into();
+    if !is_attendee_auth(e, &invoker, attendee) {
+        panic!("not authorized by attendee");
+    }
+}
+
+// Checks for an attendee settling their own payout, finalizing the distribution if needed
+fn check_own_payout(env: &Env, attendee: &Identifier) -> Attendee {
+    check_attendee_auth(env, attendee);
+
+    if !has_attendee(env, attendee) {
+        panic!("attendee did not register");
+    }
+
+    let att_struct = read_attendee(env, attendee);
+    if !att_struct.attended()
+    {
+        panic!("attendee did not attend")
+    }
+
+    if att_struct.refunded()
+    {
+        panic!("attendee already refunded")
+    }
+    check_not_frozen(&att_struct);
+
+    if feedback_pending(env, &att_struct)
+    {
+        panic!("feedback required")
+    }
+
+    check_claims_open(env);
+    if !is_finalized(env) {
+        fail(Error::EventNotFinalized);
+    }
+    att_struct
 }
 
-// TODO: add pricing tiers (can be set by admin)
-#[derive(Clone)]
-#[contracttype]
-pub enum DataKey {
-    Admin,
-    Attendee,
-    Count,
-    Unclaimed,
-    Price,
-    Token
+// Public key of an attendee checking in with a signature
+fn ed25519_key(attendee: &Identifier) -> BytesN<32> {
+    match attendee {
+        Identifier::Ed25519(key) => key.clone(),
+        _ => panic!("attendee must be an ed25519 key"),
+    }
 }
 
-pub struct DistributionContract;
+fn check_can_attend(env: &Env, attendee: &Identifier) -> Attendee {
+    if let Some(error) = attendance_error(env, attendee) {
+        fail(error);
+    }
+    read_attendee(env, attendee)
+}
 
-fn get_price(e: &Env) -> i128 {
-    e.storage().get_unchecked(DataKey::Price).unwrap()
+// First check recording the attendance of `attendee` fails, if any
+fn attendance_error(env: &Env, attendee: &Identifier) -> Option<Error> {
+    if *attendee == read_administrator(env) {
+        return Some(Error::AdminAttend);
+    }
+    if is_finalized(env) {
+        return Some(Error::Finalized);
+    }
+
+    if let Some(schedule) = get_schedule(env) {
+        let now = env.ledger().timestamp();
+        if now < schedule.event_at {
+            return Some(Error::NotStarted);
+        }
+        if now >= schedule.claim_open + get_grace(env) {
+            return Some(Error::CheckInClosed);
+        }
+    }
+
+    if !has_attendee(env, attendee) {
+        return Some(Error::NotRegistered);
+    }
+
+    let stored_att: Attendee = read_attendee(env, attendee);
+    if stored_att.attended() {
+        return Some(Error::AlreadyAttended);
+    }
+    if stored_att.due > 0 {
+        return Some(Error::DepositIncomplete);
+    }
+    None
 }
 
-fn get_token(e: &Env) -> BytesN<32> {
-    e.storage().get_unchecked(DataKey::Token).unwrap()
+fn record_attendance(env: &Env, attendee: Identifier, stored_att: Attendee, evidence: Option<BytesN<32>>) -> u32 {
+    let index = check_in(env, attendee, stored_att, evidence);
+    // Lets staff know the door list is complete
+    if remaining_unchecked(env) == 0
+    {
+        env.events().publish(EVT_V1_ALL_IN, get_attended(env));
+    }
+    index
 }
 
-fn get_count(e: &Env) -> u32 {
-    e.storage().get_unchecked(DataKey::Count).unwrap()
+// Registrants who haven't checked in yet
+fn remaining_unchecked(env: &Env) -> u32 {
+    let registered: u32 = env.storage().get_unchecked(DataKey::Registered).unwrap();
+    registered.saturating_sub(get_attended(env))
 }
 
-fn get_unclaimed(e: &Env) -> i128 {
-    e.storage().get_unchecked(DataKey::Unclaimed).unwrap()
+// Mark a registered attendee as present and give them a withdrawal index, unless comped
+fn check_in(env: &Env, attendee: Identifier, mut stored_att: Attendee, evidence: Option<BytesN<32>>) -> u32 {
+    // Standby arrivals at an overbooked event leave with their deposit and stay out of the payout
+    if venue_full(env)
+    {
+        cancel_registration(env, &attendee, stored_att);
+        env.events().publish((symbol!("standby"),), attendee);
+        return NO_WITHDRAWAL_INDEX;
+    }
+    take_receipt(env, &attendee);
+
+    let fee = stored_att.fee;
+    let comped = stored_att.comped();
+    if stored_att.disputed() {
+        stored_att.clear_flag(FLAG_DISPUTED);
+        close_dispute(env);
+    }
+
+    // Late arrivals get their deposit back at once and no share of the forfeited pool
+    if !comped && is_late(env)
+    {
+        stored_att.set_flag(FLAG_ATTENDED | FLAG_REFUNDED | FLAG_LATE);
+        stored_att.evidence = evidence;
+        write_attendee(env, &attendee, stored_att.clone());
+        env.events().publish(EVT_V1_ATTEND, AttendEvent { attendee: attendee.clone(), index: NO_WITHDRAWAL_INDEX });
+
+        journal::set(env, DataKey::Unclaimed, get_unclaimed(env) - fee);
+        journal::set(env, DataKey::Late, get_late(env) + 1);
+        pay_back(env, &get_token(env), &attendee, &stored_att, fee, symbol!("refund"));
+        env.events().publish(EVT_V1_REFUND, TransferEvent { to: attendee, amount: fee });
+        return NO_WITHDRAWAL_INDEX;
+    }
+    stored_att.set_flag(FLAG_ATTENDED);
+    stored_att.evidence = evidence;
+    stored_att.arrived_at = env.ledger().timestamp();
+    write_attendee(env, &attendee, stored_att);
+
+    let mut index = get_count(env);
+    if comped
+    {
+        index = NO_WITHDRAWAL_INDEX;
+    }
+    env.events().publish(EVT_V1_ATTEND, AttendEvent { attendee: attendee.clone(), index });
+
+    // Comped attendees are counted but stay out of the payout
+    if comped
+    {
+        let comp_attended = get_comp_attended(env);
+        journal::set(env, DataKey::CompAtt, comp_attended + 1);
+        return NO_WITHDRAWAL_INDEX;
+    }
+
+    if env.storage().has(DataKey::Referrer(attendee.clone()))
+    {
+        let referrer: Identifier = env.storage().get_unchecked(DataKey::Referrer(attendee.clone())).unwrap();
+        set_tracked(env, DataKey::Referrals(referrer.clone()), get_referrals(env, &referrer) + 1);
+        journal::set(env, DataKey::Referred, get_referred(env) + 1);
+    }
+
+    // Store withdrawal ID
+    let mut withdrawals = get_withdrawals(env);
+    withdrawals.push_back(attendee);
+    let count = withdrawals.len();
+    journal::set(env, DataKey::Withdraws, withdrawals);
+
+    if env.storage().has(DataKey::CheckptN)
+    {
+        let n: u32 = env.storage().get_unchecked(DataKey::CheckptN).unwrap();
+        if n > 0 && count % n == 0
+        {
+            let registered: u32 = env.storage().get_unchecked(DataKey::Registered).unwrap();
+            env.events().publish((symbol!("checkpoint"),), (registered, count));
+        }
+    }
+
+    // Decrement unclaimed 
+    let mut unclaimed: i128 = get_unclaimed(env);
+
+    // Decrement and save unclaimed
+    unclaimed -= fee;
+    journal::set(env, DataKey::Unclaimed, unclaimed);
+
+    let attended_fees = get_attended_fees(env);
+    journal::set(env, DataKey::AttFees, attended_fees + fee);
+
+    count - 1
 }
 
-fn has_administrator(e: &Env) -> bool {
-    let key = DataKey::Admin;
-    e.storage().has(key)
+fn set_frozen(e: &Env, id: &Identifier, frozen: bool) {
+    if !has_attendee(e, id) {
+        panic!("attendee did not register");
+    }
+
+    let mut att = read_attendee(e, id);
+    let mut frozen_ids = get_frozen_ids(e);
+    let index = frozen_ids.first_index_of(id);
+    if frozen {
+        att.set_flag(FLAG_FROZEN);
+        if index.is_none() {
+            frozen_ids.push_back(id.clone());
+        }
+    } else {
+        att.clear_flag(FLAG_FROZEN);
+        if let Some(index) = index {
+            frozen_ids.remove(index);
+        }
+    }
+    write_attendee(e, id, att);
+    journal::set(e, DataKey::FrozenIds, frozen_ids);
 }
 
-fn read_administrator(e: &Env) -> Identifier {
-    let key = DataKey::Admin;
-    e.storage().get_unchecked(key).unwrap()
+fn get_frozen_ids(e: &Env) -> Vec<Identifier> {
+    if !e.storage().has(DataKey::FrozenIds) {
+        return Vec::new(e);
+    }
+    e.storage().get_unchecked(DataKey::FrozenIds).unwrap()
 }
 
-fn write_administrator(e: &Env, id: Identifier) {
-    let key = DataKey::Admin;
-    e.storage().set(key, id);
+// Hold back what frozen attendees are still owed, so the sweep can't take it.
+// Disputes block finalization, so only freezes can be open at this point.
+fn reserve_frozen(e: &Env) -> i128 {
+    let withdrawals = get_withdrawals(e);
+    let mut total = 0;
+    for id in get_frozen_ids(e).iter() {
+        let id = id.unwrap();
+        if !has_attendee(e, &id) {
+            continue;
+        }
+        let att = read_attendee(e, &id);
+        let index = withdrawals.first_index_of(&id);
+        if att.refunded() || index.is_none() {
+            continue;
+        }
+        let amount = payout_amount(e, index.unwrap(), &att) - att.claimed;
+        set_tracked(e, DataKey::Reserved(id), amount);
+        total += amount;
+    }
+    total
 }
 
-pub fn check_admin(e: &Env, auth_id: &Identifier) {
-    if *auth_id != read_administrator(e) {
-        panic!("not authorized by admin")
+fn get_reserved(e: &Env, id: &Identifier) -> i128 {
+    if !e.storage().has(DataKey::Reserved(id.clone())) {
+        return 0;
     }
+    e.storage().get_unchecked(DataKey::Reserved(id.clone())).unwrap()
 }
 
-#[contractimpl]
-impl DistributionContract {
+fn check_not_frozen(att: &Attendee) {
+    if att.frozen() {
+        panic!("attendee frozen");
+    }
+}
 
-    pub fn initialize(
-        e: Env,
-        admin: Identifier,
-        price: i128,
-        token: BytesN<32>
-    ) {
-        if has_administrator(&e) {
-            panic!("admin is already set");
+fn get_open_disputes(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::Disputes) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Disputes).unwrap()
+}
+
+fn close_dispute(e: &Env) {
+    journal::set(e, DataKey::Disputes, get_open_disputes(e) - 1);
+}
+
+// Delete what is stored for the registration with index `index` while closing the contract
+fn remove_registration(e: &Env, index: u32) {
+    if e.storage().has(DataKey::Tombstone(index)) {
+        journal::remove(e, DataKey::Tombstone(index));
+    }
+    if !e.storage().has(DataKey::RegId(index)) {
+        return;
+    }
+    let id: Identifier = e.storage().get_unchecked(DataKey::RegId(index)).unwrap();
+    journal::remove(e, DataKey::RegId(index));
+    if has_attendee(e, &id) && read_attendee(e, &id).reg_index == index {
+        journal::remove(e, DataKey::AttRec(id.clone()));
+        unbind_ticket(e, &id);
+    }
+}
+
+fn get_credit(e: &Env, id: &Identifier) -> i128 {
+    if !e.storage().has(DataKey::Credit(id.clone())) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Credit(id.clone())).unwrap()
+}
+
+fn get_credited(e: &Env) -> i128 {
+    if !e.storage().has(DataKey::Credited) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::Credited).unwrap()
+}
+
+fn get_tracked_count(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::TrackedN) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::TrackedN).unwrap()
+}
+
+// Write a key derived from an identifier or name, remembering it for `close` the first time
+fn set_tracked<V: IntoVal<Env, RawVal>>(e: &Env, key: DataKey, val: V) {
+    if !e.storage().has(key.clone()) {
+        let count = get_tracked_count(e);
+        journal::set(e, DataKey::Tracked(count), key.clone());
+        journal::set(e, DataKey::TrackedN, count + 1);
+    }
+    journal::set(e, key, val);
+}
+
+// Delete the configuration and counters while closing the contract. Keys derived from
+// identifiers or names are removed from their `Tracked` entries before.
+fn remove_config(e: &Env) {
+    let tiers: Vec<u32> = e.storage().get_unchecked(DataKey::Tiers).unwrap();
+    for tier in tiers.iter() {
+        journal::remove(e, DataKey::Tier(tier.unwrap()));
+    }
+
+    let keys = vec![
+        e,
+        DataKey::Withdraws, DataKey::Unclaimed, DataKey::Price, DataKey::Token,
+        DataKey::InstDue, DataKey::Registered, DataKey::Remainder, DataKey::Benef,
+        DataKey::Swept, DataKey::Count, DataKey::StorageVer, DataKey::AllowMode,
+        DataKey::Oracle, DataKey::UsdPrice, DataKey::AttFees, DataKey::PaidOut,
+        DataKey::Refunds, DataKey::Tiers, DataKey::Held, DataKey::CheckptN,
+        DataKey::NextReg, DataKey::NoContract, DataKey::AuditCount, DataKey::Approvers,
+        DataKey::Threshold, DataKey::ProposalN, DataKey::Pending, DataKey::Timelock,
+        DataKey::Vesting, DataKey::FinalAt, DataKey::SponsPool, DataKey::MatchBps,
+        DataKey::MatchCap, DataKey::MatchRate, DataKey::Prizes, DataKey::Winners,
+        DataKey::Surcharge, DataKey::Comped, DataKey::CompAtt, DataKey::BonusCap,
+        DataKey::FeedReq, DataKey::Disputes, DataKey::Arbiter, DataKey::Quorum,
+        DataKey::YieldPool, DataKey::Supplied, DataKey::Splits, DataKey::FeesOwed,
+        DataKey::Order, DataKey::Purged, DataKey::LedgerN, DataKey::Nonce,
+        DataKey::Capacity, DataKey::Overbook, DataKey::Processed,
+        DataKey::AutoKey, DataKey::Donated, DataKey::Swap,
+        DataKey::MerkleCur, DataKey::MerkleN, DataKey::Frontier, DataKey::MerkleRoot,
+        DataKey::Rsvp, DataKey::Bounds, DataKey::ReceiptTok,
+        DataKey::Schedule, DataKey::Decimals, DataKey::WholeUnits, DataKey::Grace,
+        DataKey::Late, DataKey::TotalTime, DataKey::Attestor, DataKey::Parent,
+        DataKey::PriceSteps, DataKey::MinRegs, DataKey::RefBonus, DataKey::RefShare,
+        DataKey::Referred, DataKey::SeatLimit, DataKey::FrozenIds, DataKey::TrackedN,
+        DataKey::Credited, DataKey::CloseCur
+    ];
+    for key in keys.iter() {
+        journal::remove(e, key.unwrap());
+    }
+}
+
+// Remove a registration before finalization and refund the fee paid
+fn cancel_registration(e: &Env, id: &Identifier, att: Attendee) {
+    take_receipt(e, id);
+    // Deleting the record rather than flagging it lets the identifier register again later
+    journal::remove(e, DataKey::AttRec(id.clone()));
+    if e.storage().has(DataKey::Referrer(id.clone())) {
+        let referrer: Identifier = e.storage().get_unchecked(DataKey::Referrer(id.clone())).unwrap();
+        journal::remove(e, DataKey::Referrer(id.clone()));
+        // Only attendees who showed up and are in the payout were counted
+        if att.attended() && !att.comped() && !att.late() {
+            journal::set(e, DataKey::Referrals(referrer.clone()), get_referrals(e, &referrer) - 1);
+            journal::set(e, DataKey::Referred, get_referred(e) - 1);
         }
+    }
+    unbind_ticket(e, id);
+    if att.disputed() {
+        close_dispute(e);
+    }
+    if att.comped() {
+        let comped: u32 = e.storage().get_unchecked(DataKey::Comped).unwrap();
+        journal::set(e, DataKey::Comped, comped - 1);
+    } else if e.storage().has(DataKey::Seats(seat_holder(id, &att.payer))) {
+        release_seat(e, &seat_holder(id, &att.payer));
+    }
 
-        write_administrator(&e, admin);
+    // Give the seat back to the tier
+    if let Some(tier_id) = att.tier {
+        let mut stored_tier = read_tier(e, tier_id);
+        stored_tier.sold -= 1;
+        journal::set(e, DataKey::Tier(tier_id), stored_tier);
+    }
+
+    if att.attended() {
+        let mut withdrawals = get_withdrawals(e);
+        if let Some(index) = withdrawals.first_index_of(id) {
+            withdrawals.remove(index);
+        }
+        journal::set(e, DataKey::Withdraws, withdrawals);
 
-        e.storage().set(DataKey::Price, price);
-        e.storage().set(DataKey::Token, token);
-        e.storage().set(DataKey::Unclaimed, 0 as i128);
-        e.storage().set(DataKey::Count, 0 as u32);
+        let attended_fees = get_attended_fees(e);
+        journal::set(e, DataKey::AttFees, attended_fees - att.fee);
+    } else {
+        let mut unclaimed: i128 = get_unclaimed(e);
+        unclaimed -= att.fee;
+        journal::set(e, DataKey::Unclaimed, unclaimed);
     }
 
-    pub fn deposit(
-        env: Env,
-        attendee: Identifier
-    ) {
-        if attendee == read_administrator(&env)
-        {
-            panic!("admin cannot deposit")
+    let registered: u32 = e.storage().get_unchecked(DataKey::Registered).unwrap();
+    journal::set(e, DataKey::Registered, registered - 1);
+
+    let token = get_token(e);
+    pay_back(e, &token, id, &att, att.fee, symbol!("refund"));
+    e.events().publish(EVT_V1_REFUND, TransferEvent { to: id.clone(), amount: att.fee });
+}
+
+// Whether a proposed sensitive action is waiting for its timelock
+fn in_exit_window(e: &Env) -> bool {
+    let pending: Vec<u32> = e.storage().get_unchecked(DataKey::Pending).unwrap();
+    for action_id in pending.iter() {
+        if e.ledger().timestamp() < read_proposal(e, action_id.unwrap()).ready_at {
+            return true;
         }
+    }
+    false
+}
 
-        let price = get_price(&env);
-        let token = get_token(&env);
+fn write_remainder_policy(e: &Env, policy: RemainderPolicy) {
+    check_not_finalized(e);
+    journal::set(e, DataKey::Remainder, policy);
+}
+
+fn read_proposal(e: &Env, action_id: u32) -> Proposal {
+    if !e.storage().has(DataKey::Proposal(action_id)) {
+        panic!("action does not exist");
+    }
+    e.storage().get_unchecked(DataKey::Proposal(action_id)).unwrap()
+}
+
+fn get_threshold(e: &Env) -> u32 {
+    if !has_approvers(e) {
+        return 1;
+    }
+    e.storage().get_unchecked(DataKey::Threshold).unwrap()
+}
+
+// Execute the proposal if it has enough approvals and its timelock has passed
+fn try_execute(e: &Env, action_id: u32, proposal: &mut Proposal) -> bool {
+    if proposal.approvals.len() < get_threshold(e) || e.ledger().timestamp() < proposal.ready_at {
+        return false;
+    }
+
+    proposal.executed = true;
+    log_admin_action(e, symbol!("execute"), None);
+    apply_action(e, proposal.action.clone());
 
-        if env.storage().has(attendee.clone()) {
-            panic!("attendee already registered");
+    let mut pending: Vec<u32> = e.storage().get_unchecked(DataKey::Pending).unwrap();
+    if let Some(index) = pending.first_index_of(action_id) {
+        pending.remove(index);
+    }
+    journal::set(e, DataKey::Pending, pending);
+    true
+}
+
+// Pay the payout reserved for a frozen attendee to the beneficiary and close their case
+fn release_reserve(e: &Env, attendee: &Identifier) -> i128 {
+    if !e.storage().has(DataKey::Reserved(attendee.clone()))
+    {
+        panic!("nothing reserved")
+    }
+    let mut att = read_attendee(e, attendee);
+    if !att.frozen()
+    {
+        panic!("attendee not frozen")
+    }
+
+    let amount = get_reserved(e, attendee);
+    journal::remove(e, DataKey::Reserved(attendee.clone()));
+    att.set_flag(FLAG_REFUNDED);
+    write_attendee(e, attendee, att);
+    let refunds: u32 = e.storage().get_unchecked(DataKey::Refunds).unwrap();
+    journal::set(e, DataKey::Refunds, refunds + 1);
+
+    if e.storage().has(DataKey::Splits)
+    {
+        journal::set(e, DataKey::FeesOwed, get_fees_owed(e) + amount);
+        return amount;
+    }
+    let beneficiary: Identifier = e.storage().get_unchecked(DataKey::Benef).unwrap();
+    transfer_from_contract_to_account(e, &get_token(e), &beneficiary, &amount, symbol!("sweep"));
+    e.events().publish(EVT_V1_SWEEP, TransferEvent { to: beneficiary, amount });
+    amount
+}
+
+fn apply_action(e: &Env, action: AdminAction) {
+    match action {
+        AdminAction::Sweep => {
+            sweep(e);
+        }
+        AdminAction::SetBenef(beneficiary) => journal::set(e, DataKey::Benef, beneficiary),
+        AdminAction::SetRemain(policy) => write_remainder_policy(e, policy),
+        AdminAction::SetBounds(min, max) => write_price_bounds(e, admin_amount(e, min), admin_amount(e, max)),
+        AdminAction::Release(attendee) => {
+            release_reserve(e, &attendee);
+        }
+        AdminAction::Cancel => {
+            check_not_finalized(e);
+            write_finalized(e, DistributionMode::Refund);
         }
+    }
+}
 
-        let attendee_struct = Attendee{fee: price, attended: false, refunded: false};
-        env.storage().set(&attendee, attendee_struct);
+fn check_can_register(env: &Env, attendee: &Identifier) {
+    if let Some(error) = registration_error(env, attendee) {
+        fail(error);
+    }
+}
 
-        let mut unclaimed: i128 = get_unclaimed(&env);
-        unclaimed += price;
-        env.storage().set(DataKey::Unclaimed, unclaimed);
+// First check a registration by `attendee` fails, if any
+fn registration_error(env: &Env, attendee: &Identifier) -> Option<Error> {
+    if *attendee == read_administrator(env) {
+        return Some(Error::AdminDeposit);
+    }
+    if is_finalized(env) {
+        return Some(Error::Finalized);
+    }
 
-        // Transfer token to this contract address.
-        transfer_from_account_to_contract(&env, &token, &attendee.into(), &price);
+    if has_attendee(env, attendee) {
+        return Some(Error::AlreadyRegistered);
     }
-    
-    pub fn attend(
-        env: Env,
-        attendee: Identifier
-    ) {
-        check_admin(&env, &env.invoker().into());
-        if attendee == read_administrator(&env)
-        {
-            panic!("admin cannot attend")
+
+    if let Some(schedule) = get_schedule(env) {
+        if env.ledger().timestamp() >= schedule.reg_end {
+            return Some(Error::RegistrationClosed);
         }
+    }
 
-        if !env.storage().has(attendee.clone()) {
-            panic!("attendee did not register");
+    if min_registrations_missed(env) {
+        return Some(Error::MinimumNotMet);
+    }
+
+    let registered: u32 = env.storage().get_unchecked(DataKey::Registered).unwrap();
+    if registered >= MAX_REGISTRATIONS {
+        return Some(Error::RegistrationCap);
+    }
+
+    if env.storage().has(DataKey::Capacity) {
+        let capacity: u32 = env.storage().get_unchecked(DataKey::Capacity).unwrap();
+        let overbook: u32 = env.storage().get_unchecked(DataKey::Overbook).unwrap();
+        if registered as u64 * 100 >= capacity as u64 * overbook as u64 {
+            return Some(Error::EventFull);
         }
+    }
 
-        let mut stored_att : Attendee = env.storage().get_unchecked(attendee.clone()).unwrap();
+    if allowlist_enabled(env) && !env.storage().has(DataKey::Allowed(attendee.clone())) {
+        return Some(Error::NotAllowlisted);
+    }
 
-        if stored_att.attended
-        {
-            panic!("attendance already recorded")
-        } 
+    if env.storage().has(DataKey::Blocked(attendee.clone())) {
+        return Some(Error::Blocked);
+    }
 
-        stored_att.attended = true;
-        env.storage().set(&attendee, stored_att);
+    if let Identifier::Contract(_) = attendee {
+        if env.storage().has(DataKey::NoContract) {
+            return Some(Error::ContractAttendee);
+        }
+    }
 
-        // Store withdrawal ID
-        let mut count: u32 = get_count(&env);
-        env.storage().set(count, attendee);
+    if env.storage().has(DataKey::Attestor) {
+        let attestor: BytesN<32> = env.storage().get_unchecked(DataKey::Attestor).unwrap();
+        if !attestation::Client::new(env, &attestor).eligible(attendee) {
+            return Some(Error::NotAttested);
+        }
+    }
+    None
+}
 
-        // Increment and save the count.
-        count += 1;
-        env.storage().set(DataKey::Count, &count);
+// Panic with the message the entrypoints have always used for `error`
+fn fail(error: Error) -> ! {
+    match error {
+        Error::AdminDeposit => panic!("admin cannot deposit"),
+        Error::Finalized => panic!("distribution already started"),
+        Error::AlreadyRegistered => panic!("attendee already registered"),
+        Error::RegistrationClosed => panic!("registration closed"),
+        Error::MinimumNotMet => panic!("minimum registrations not met"),
+        Error::RegistrationCap => panic!("registration cap reached"),
+        Error::EventFull => panic!("event full"),
+        Error::NotAllowlisted => panic!("attendee not on allowlist"),
+        Error::Blocked => panic!("attendee is blocked"),
+        Error::ContractAttendee => panic!("contract attendees not allowed"),
+        Error::NotAttested => panic!("attendee not attested"),
+        Error::NoTier => panic!("tier does not exist"),
+        Error::TierSoldOut => panic!("tier sold out"),
+        Error::AllowanceTooLow => panic!("allowance too low"),
+        Error::AdminAttend => panic!("admin cannot attend"),
+        Error::NotStarted => panic!("event not started"),
+        Error::CheckInClosed => panic!("check-in closed"),
+        Error::NotRegistered => panic!("attendee did not register"),
+        Error::AlreadyAttended => panic!("attendance already recorded"),
+        Error::DepositIncomplete => panic!("deposit not complete"),
+        Error::SeatLimit => panic!("seat limit reached"),
+        Error::EventNotFinalized => panic!("event not finalized"),
+        Error::Comped => panic!("comped attendees are not paid out")
+    }
+}
 
-        // Decrement unclaimed 
-        let mut unclaimed: i128 = get_unclaimed(&env);
-        let price = get_price(&env);
+fn get_seats(env: &Env, holder: &Identifier) -> u32 {
+    if !env.storage().has(DataKey::Seats(holder.clone())) {
+        return 0;
+    }
+    env.storage().get_unchecked(DataKey::Seats(holder.clone())).unwrap()
+}
 
-        // Decrement and save unclaimed
-        unclaimed -= price;
-        env.storage().set(DataKey::Unclaimed, unclaimed);
+fn seat_available(env: &Env, holder: &Identifier) -> bool {
+    if !env.storage().has(DataKey::SeatLimit) {
+        return true;
+    }
+    let limit: u32 = env.storage().get_unchecked(DataKey::SeatLimit).unwrap();
+    get_seats(env, holder) < limit
+}
 
+// Seats count against whoever pays for them: the payer or captain if any, else the attendee
+fn seat_holder(attendee: &Identifier, payer: &Option<Identifier>) -> Identifier {
+    match payer {
+        Some(payer) => payer.clone(),
+        None => attendee.clone()
     }
+}
 
-    // Distribute the money to a batch of attendees
-    pub fn withdraw(
-        env: Env,
-        high: u32,
-        low: u32,
-    ) -> i32 {
-        // TODO: once withdrawal started, deposit and attend should not be allowed
-        check_admin(&env, &env.invoker().into());
+fn take_seat(env: &Env, holder: &Identifier) {
+    if !seat_available(env, holder) {
+        fail(Error::SeatLimit);
+    }
+    set_tracked(env, DataKey::Seats(holder.clone()), get_seats(env, holder) + 1);
+}
 
-        // Each function call is limited in resources, so we limit how many attendees can receive funds in one call
-        if high < low || high - low > 10
-        {
-            panic!("Invalid range")
+fn release_seat(env: &Env, holder: &Identifier) {
+    let seats = get_seats(env, holder);
+    if seats <= 1 {
+        journal::remove(env, DataKey::Seats(holder.clone()));
+    } else {
+        journal::set(env, DataKey::Seats(holder.clone()), seats - 1);
+    }
+}
+
+// Full price of a registration in `tier`, or at the base price
+fn registration_price(env: &Env, tier: Option<u32>) -> i128 {
+    match tier {
+        Some(tier_id) => {
+            let stored_tier = read_tier(env, tier_id);
+            if stored_tier.sold >= stored_tier.capacity {
+                panic!("tier sold out");
+            }
+            stored_tier.price
         }
+        None => current_price(env)
+    }
+}
 
-        let price = get_price(&env);
-        let token = get_token(&env);
-        let withdrawal_count = get_count(&env);
-        let unclaimed = get_unclaimed(&env);
+// `price` less the bundle discount when `attendee` is registered for the parent event
+fn bundle_price(env: &Env, attendee: &Identifier, price: i128) -> i128 {
+    if !env.storage().has(DataKey::Parent) {
+        return price;
+    }
+    let (parent, discount_bps): (BytesN<32>, u32) = env.storage().get_unchecked(DataKey::Parent).unwrap();
+    if !parent_event::Client::new(env, &parent).is_reg(attendee) {
+        return price;
+    }
+    price - price * discount_bps as i128 / 10_000
+}
 
-        let distribution_amount = price + unclaimed.checked_div(withdrawal_count as i128).unwrap();
-        
-        // TODO: currently, the remainder is left in the contract without an option to withdraw
-        let mut refund_count = 0;
-        for id in low..high {
-            if !env.storage().has(id)
-            {
-                continue;
-            }
+// With installments, the first half is paid now and the remainder later
+fn upfront_amount(env: &Env, price: i128) -> i128 {
+    if installments_open(env) {
+        return price / 2;
+    }
+    price
+}
 
-            let att : Identifier = env.storage().get_unchecked(id).unwrap();
-            let mut att_struct : Attendee = env.storage().get_unchecked(&att).unwrap();
+// Late registration surcharge on `price`, if the surcharge window has started
+fn surcharge_amount(env: &Env, price: i128) -> i128 {
+    if !env.storage().has(DataKey::Surcharge) {
+        return 0;
+    }
+    let surcharge: Surcharge = env.storage().get_unchecked(DataKey::Surcharge).unwrap();
+    if env.ledger().timestamp() < surcharge.starts_at {
+        return 0;
+    }
+    price * surcharge.bps as i128 / 10_000
+}
 
-            if !att_struct.refunded
-            {
-                transfer_from_contract_to_account(&env, &token, &att, &distribution_amount);
-                att_struct.refunded = true;
-                env.storage().set(att, att_struct);
-                refund_count += 1
+// With `prepaid`, the caller already has the amount in the contract, from the attendee's signed
+// transfer or their reported payment, instead of pulling it via allowance.
+// Returns the registration index and the amount charged.
+fn register(env: &Env, attendee: Identifier, tier: Option<u32>, payer: Option<Identifier>, payer_bps: u32, prepaid: bool) -> (u32, i128) {
+    check_can_register(env, &attendee);
+    take_seat(env, &seat_holder(&attendee, &payer));
+
+    let token = get_token(env);
+    let price = bundle_price(env, &attendee, registration_price(env, tier));
+    let mut amount = upfront_amount(env, price);
+
+    // The surcharge is paid upfront, either into the refundable deposit or straight to the beneficiary
+    let surcharge = surcharge_amount(env, price);
+    let mut routed = 0;
+    if surcharge > 0 {
+        let config: Surcharge = env.storage().get_unchecked(DataKey::Surcharge).unwrap();
+        if config.to_benef {
+            routed = surcharge;
+        } else {
+            amount += surcharge;
+        }
+    }
+
+    if let Some(tier_id) = tier {
+        let mut stored_tier = read_tier(env, tier_id);
+        stored_tier.sold += 1;
+        journal::set(env, DataKey::Tier(tier_id), stored_tier);
+    }
+
+    let reg_index = next_reg_index(env, &attendee);
+    let attendee_struct = Attendee{fee: amount, due: price - upfront_amount(env, price), flags: 0, evidence: None, tier, reg_index, claimed: 0, feedback: None, payer, payer_bps, contact: None, arrived_at: 0, left_at: 0, princ_paid: 0, bonus_paid: 0};
+    write_attendee(env, &attendee, attendee_struct.clone());
+    issue_receipt(env, &attendee);
+
+    let mut unclaimed: i128 = get_unclaimed(env);
+    unclaimed += amount;
+    journal::set(env, DataKey::Unclaimed, unclaimed);
+
+    let registered: u32 = env.storage().get_unchecked(DataKey::Registered).unwrap();
+    journal::set(env, DataKey::Registered, registered + 1);
+
+    if prepaid {
+        // The caller already took `amount` plus any routed surcharge from the attendee
+        journal::set(env, DataKey::Held, get_held(env) + amount + routed);
+        ledger::record(env, symbol!("deposit"), attendee.clone(), amount);
+        if routed > 0 {
+            ledger::record(env, symbol!("fee"), attendee.clone(), routed);
+        }
+    } else {
+        // Transfer token to this contract address.
+        collect(env, &token, &attendee, &attendee_struct, amount, symbol!("deposit"));
+    }
+
+    if routed > 0 && env.storage().has(DataKey::Splits) {
+        if !prepaid {
+            collect(env, &token, &attendee, &attendee_struct, routed, symbol!("fee"));
+        }
+        journal::set(env, DataKey::FeesOwed, get_fees_owed(env) + routed);
+    } else if routed > 0 && prepaid {
+        let beneficiary: Identifier = env.storage().get_unchecked(DataKey::Benef).unwrap();
+        transfer_from_contract_to_account(env, &token, &beneficiary, &routed, symbol!("fee"));
+    } else if routed > 0 {
+        let beneficiary: Identifier = env.storage().get_unchecked(DataKey::Benef).unwrap();
+        let client = token::Client::new(env, &token);
+        for (from, part) in split_amount(&attendee, &attendee_struct, routed) {
+            if part > 0 {
+                client.xfer_from(&Signature::Invoker, &0, &from, &beneficiary, &part);
             }
         }
-        refund_count
     }
+    env.events().publish(EVT_V1_DEPOSIT, DepositEvent { attendee, reg_index, amount: amount + routed });
+    (reg_index, amount + routed)
+}
+
+// Stands in for erased attendees in the withdrawal list; never has a record
+fn erased_id(e: &Env) -> Identifier {
+    Identifier::Contract(BytesN::from_array(e, &[0; 32]))
 }
 
 fn get_contract_id(e: &Env) -> Identifier {
@@ -211,9 +5316,35 @@ fn transfer_from_account_to_contract(
     token_id: &BytesN<32>,
     from: &Identifier,
     amount: &i128,
+    kind: Symbol,
 ) {
+    if *amount == 0 {
+        return;
+    }
     let client = token::Client::new(e, token_id);
     client.xfer_from(&Signature::Invoker, &0, from, &get_contract_id(e), amount);
+    journal::set(e, DataKey::Held, get_held(e) + amount);
+    ledger::record(e, kind, from.clone(), *amount);
+}
+
+// Like `transfer_from_contract_to_account`, but returns false instead of trapping when the token refuses
+fn try_transfer_from_contract_to_account(
+    e: &Env,
+    token_id: &BytesN<32>,
+    to: &Identifier,
+    amount: &i128,
+    kind: Symbol,
+) -> bool {
+    if *amount == 0 {
+        return true;
+    }
+    let client = token::Client::new(e, token_id);
+    if !matches!(client.try_xfer(&Signature::Invoker, &0, to, amount), Ok(Ok(_))) {
+        return false;
+    }
+    journal::set(e, DataKey::Held, get_held(e) - amount);
+    ledger::record(e, kind, to.clone(), -amount);
+    true
 }
 
 fn transfer_from_contract_to_account(
@@ -221,9 +5352,18 @@ fn transfer_from_contract_to_account(
     token_id: &BytesN<32>,
     to: &Identifier,
     amount: &i128,
+    kind: Symbol,
 ) {
+    if *amount == 0 {
+        return;
+    }
     let client = token::Client::new(e, token_id);
     client.xfer(&Signature::Invoker, &0, to, amount);
+    journal::set(e, DataKey::Held, get_held(e) - amount);
+    ledger::record(e, kind, to.clone(), -amount);
 }
 
+#[cfg(feature = "client")]
+pub mod client;
+
 mod test;
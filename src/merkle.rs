@@ -0,0 +1,70 @@
+// Append-only Merkle tree of fixed depth, padded with zero leaves. Only the rightmost node
+// of every level is kept, so appending costs `DEPTH` hashes whatever the number of leaves.
+
+use crate::journal;
+use crate::DataKey;
+use soroban_sdk::{Bytes, BytesN, Env, Vec};
+
+// Holds `MAX_REGISTRATIONS` leaves
+pub const DEPTH: u32 = 14;
+
+fn hash_pair(e: &Env, left: &BytesN<32>, right: &BytesN<32>) -> BytesN<32> {
+    let mut data: Bytes = left.clone().into();
+    data.append(&right.clone().into());
+    e.crypto().sha256(&data)
+}
+
+pub fn count(e: &Env) -> u32 {
+    if !e.storage().has(DataKey::MerkleN) {
+        return 0;
+    }
+    e.storage().get_unchecked(DataKey::MerkleN).unwrap()
+}
+
+fn frontier(e: &Env) -> Vec<BytesN<32>> {
+    if e.storage().has(DataKey::Frontier) {
+        return e.storage().get_unchecked(DataKey::Frontier).unwrap();
+    }
+    let mut nodes = Vec::new(e);
+    for _ in 0..DEPTH {
+        nodes.push_back(BytesN::from_array(e, &[0; 32]));
+    }
+    nodes
+}
+
+pub fn append(e: &Env, leaf: BytesN<32>) {
+    let count = count(e);
+    if count >= 1 << DEPTH {
+        panic!("merkle tree full");
+    }
+
+    let mut nodes = frontier(e);
+    let mut node = leaf;
+    let mut size = count;
+    let mut level = 0;
+    while size & 1 == 1 {
+        node = hash_pair(e, &nodes.get_unchecked(level).unwrap(), &node);
+        size >>= 1;
+        level += 1;
+    }
+    nodes.set(level, node);
+    journal::set(e, DataKey::Frontier, nodes);
+    journal::set(e, DataKey::MerkleN, count + 1);
+}
+
+pub fn root(e: &Env) -> BytesN<32> {
+    let nodes = frontier(e);
+    let mut node = BytesN::from_array(e, &[0; 32]);
+    let mut zero = node.clone();
+    let mut size = count(e);
+    for level in 0..DEPTH {
+        if size & 1 == 1 {
+            node = hash_pair(e, &nodes.get_unchecked(level).unwrap(), &node);
+        } else {
+            node = hash_pair(e, &node, &zero);
+        }
+        zero = hash_pair(e, &zero, &zero);
+        size >>= 1;
+    }
+    node
+}
@@ -1,8 +1,9 @@
 #![cfg(test)]
 
 use super::*;
-use soroban_sdk::testutils::{Accounts, Ledger, LedgerInfo};
-use soroban_sdk::{AccountId, Env, IntoVal};
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use soroban_sdk::testutils::{Accounts, Events, Ledger, LedgerInfo};
+use soroban_sdk::{AccountId, Env, IntoVal, RawVal};
 
 soroban_sdk::contractimport!(
     file = "target/wasm32-unknown-unknown/release/soroban_token_contract.wasm"
@@ -10,6 +11,86 @@ soroban_sdk::contractimport!(
 
 type TokenClient = Client;
 
+#[cfg(feature = "bench")]
+mod bench;
+mod fuzz;
+mod mock_token;
+
+use mock_token::{create_mock_token, MockTokenClient};
+
+// Quotes 150 token units per USD
+pub struct MockOracle;
+
+#[contractimpl]
+impl MockOracle {
+    pub fn price(_env: Env) -> i128 {
+        150
+    }
+}
+
+// Pays 10% on top of whatever was supplied
+pub struct MockPool;
+
+#[contractimpl]
+impl MockPool {
+    pub fn init(env: Env, token: BytesN<32>) {
+        env.storage().set(symbol!("token"), token);
+    }
+
+    pub fn deposit(env: Env, from: Identifier, amount: i128) {
+        let token: BytesN<32> = env.storage().get_unchecked(symbol!("token")).unwrap();
+        let pool = Identifier::Contract(env.get_current_contract());
+        TokenClient::new(&env, &token).xfer_from(&Signature::Invoker, &0, &from, &pool, &amount);
+        env.storage().set(symbol!("supplied"), amount);
+    }
+
+    pub fn redeem(env: Env, to: Identifier) -> i128 {
+        let token: BytesN<32> = env.storage().get_unchecked(symbol!("token")).unwrap();
+        let supplied: i128 = env.storage().get_unchecked(symbol!("supplied")).unwrap();
+        let amount = supplied + supplied / 10;
+        TokenClient::new(&env, &token).xfer(&Signature::Invoker, &0, &to, &amount);
+        amount
+    }
+}
+
+// Buys one unit of the target token for every two sold, out of its own stock
+pub struct MockSwap;
+
+#[contractimpl]
+impl MockSwap {
+    pub fn swap(
+        env: Env,
+        from: Identifier,
+        to: Identifier,
+        sell: BytesN<32>,
+        buy: BytesN<32>,
+        amount: i128,
+        min_out: i128,
+    ) -> i128 {
+        let out = amount / 2;
+        if out < min_out {
+            panic!("slippage");
+        }
+        let swap = Identifier::Contract(env.get_current_contract());
+        TokenClient::new(&env, &sell).xfer_from(&Signature::Invoker, &0, &from, &swap, &amount);
+        TokenClient::new(&env, &buy).xfer(&Signature::Invoker, &0, &to, &out);
+        out
+    }
+}
+
+// Vouches for accounts only
+pub struct MockAttestor;
+
+#[contractimpl]
+impl MockAttestor {
+    pub fn eligible(_env: Env, id: Identifier) -> bool {
+        match id {
+            Identifier::Account(_) => true,
+            _ => false,
+        }
+    }
+}
+
 fn create_token_contract(e: &Env, admin: &AccountId) -> (BytesN<32>, TokenClient) {
     e.install_contract_wasm(WASM);
 
@@ -27,7 +108,7 @@ fn create_token_contract(e: &Env, admin: &AccountId) -> (BytesN<32>, TokenClient
 
 fn create_distribution_contract(e: &Env, admin: &AccountId, token: BytesN<32>) -> DistributionContractClient {
     let distr = DistributionContractClient::new(e, e.register_contract(None, DistributionContract {}));
-    distr.initialize(&Identifier::Account(admin.clone()), &200, &token);
+    distr.initialize(&InitConfig::new(Identifier::Account(admin.clone()), 200, token));
     distr
 }
 
@@ -41,6 +122,10 @@ struct DistributionTest {
 impl DistributionTest {
 
     fn setup() -> Self {
+        Self::setup_with_token(create_token_contract)
+    }
+
+    fn setup_with_token(create_token: fn(&Env, &AccountId) -> (BytesN<32>, TokenClient)) -> Self {
         let env: Env = Default::default();
         env.ledger().set(LedgerInfo {
             timestamp: 12345,
@@ -58,7 +143,7 @@ impl DistributionTest {
 
         let token_admin = env.accounts().generate();
 
-        let (token_id, token) = create_token_contract(&env, &token_admin);
+        let (token_id, token) = create_token(&env, &token_admin);
         for attendee in attendee_users.clone() {
             token.with_source_account(&token_admin).mint(
                 &Signature::Invoker,
@@ -92,15 +177,15 @@ impl DistributionTest {
         self.call_attend(attendee);
     }
 
-    fn withdraw(&self, high: u32, low: u32) -> i32 {
-        self.call_withdraw(high, low)
+    fn withdraw(&self, high: u32, low: u32) -> u32 {
+        self.call_withdraw(high, low).paid
     }
 
     fn call_deposit(
         &self,
         attendee: &Identifier,
     ) {
-        self.contract.deposit(attendee);
+        self.contract.deposit(attendee, &None);
     }
 
     fn account_id_to_identifier(&self, account_id: &AccountId) -> Identifier {
@@ -109,7 +194,7 @@ impl DistributionTest {
 
     fn call_withdraw(
         &self, high: u32, low: u32
-    ) -> i32 {
+    ) -> BatchResult {
         self.contract.with_source_account(&self.token_admin).withdraw(&high, &low)
     }
 
@@ -117,7 +202,42 @@ impl DistributionTest {
         &self,
         attendee: &Identifier
     ) {
-        self.contract.with_source_account(&self.token_admin).attend(attendee);
+        self.contract.with_source_account(&self.token_admin).attend(attendee, &None);
+    }
+
+    // Generate an extra funded account beyond the three default attendees
+    fn new_user(&self) -> AccountId {
+        let user = self.contract.env.accounts().generate();
+        self.token.with_source_account(&self.token_admin).mint(
+            &Signature::Invoker,
+            &0,
+            &Identifier::Account(user.clone()),
+            &1000,
+        );
+        user
+    }
+
+    fn set_timestamp(&self, timestamp: u64) {
+        self.set_ledger(timestamp, 10);
+    }
+
+    // The raffle seed is taken from the ledger, so fixing both fixes the draw
+    fn set_ledger(&self, timestamp: u64, sequence_number: u32) {
+        self.contract.env.ledger().set(LedgerInfo {
+            timestamp,
+            protocol_version: 1,
+            sequence_number,
+            network_passphrase: Default::default(),
+            base_reserve: 10,
+        });
+    }
+
+    // Withdrawal indexes the raffle would draw if finalized now
+    fn expected_raffle_winners(&self, prizes: u32) -> Vec<u32> {
+        let env = &self.contract.env;
+        env.as_contract(&self.contract.contract_id, || {
+            draw_winners(env, &raffle_seed(env), prizes, get_count(env))
+        })
     }
 
     fn approve_deposit(&self, amount: u32, user: AccountId) {
@@ -148,7 +268,7 @@ fn test_unauthorized_attendance() {
     let test = DistributionTest::setup();
 
     // Attendee can't trigger attendance counting
-    test.contract.with_source_account(&test.attendee_users[0].clone()).attend(&test.account_id_to_identifier(&test.attendee_users[0].clone()));
+    test.contract.with_source_account(&test.attendee_users[0].clone()).attend(&test.account_id_to_identifier(&test.attendee_users[0].clone()), &None);
 }
 
 #[test]
@@ -365,4 +485,3100 @@ fn test_batched_withdrawal() {
         1100
     );
 
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_installment_deposit() {
+    let test = DistributionTest::setup();
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.contract.with_source_account(&test.token_admin).set_inst(&20000);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+
+    // Only half is charged upfront
+    test.deposit(&user);
+    assert_eq!(test.token.balance(&user), 900);
+
+    test.contract.complete(&user);
+    assert_eq!(test.token.balance(&user), 800);
+
+    test.attend(&user);
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&user), 1000);
+}
+
+#[test]
+#[should_panic(expected = "installment deadline passed")]
+fn test_installment_deadline_passed() {
+    let test = DistributionTest::setup();
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.contract.with_source_account(&test.token_admin).set_inst(&20000);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user);
+
+    test.set_timestamp(20001);
+    test.contract.complete(&user);
+}
+
+#[test]
+fn test_incomplete_installment_forfeited() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.contract.with_source_account(&test.token_admin).set_inst(&20000);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.contract.complete(&user0);
+
+    // User1 never pays the remainder, so their first half goes to User0
+    test.set_timestamp(20001);
+    test.attend(&user0);
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&user0), 1100);
+    assert_eq!(test.token.balance(&user1), 900);
+}
+
+#[test]
+fn test_attendance_evidence() {
+    let test = DistributionTest::setup();
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+    let evidence = BytesN::from_array(&test.contract.env, &[7; 32]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user);
+    assert_eq!(test.contract.attendee(&user).evidence, None);
+
+    test.contract.with_source_account(&test.token_admin).attend(&user, &Some(evidence.clone()));
+    let stored = test.contract.attendee(&user);
+    assert!(stored.attended());
+    assert_eq!(stored.evidence, Some(evidence));
+}
+
+#[test]
+fn test_simulate_distribution() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    test.attend(&user0);
+    test.attend(&user2);
+
+    let payouts = test.contract.simulate(&5, &0);
+    assert_eq!(payouts.len(), 2);
+    assert_eq!(payouts.get_unchecked(0).unwrap(), (user0.clone(), 300));
+    assert_eq!(payouts.get_unchecked(1).unwrap(), (user2.clone(), 300));
+
+    // Nothing was transferred
+    assert_eq!(test.token.balance(&user0), 800);
+
+    // Refunded attendees are no longer included
+    test.withdraw(1, 0);
+    let payouts = test.contract.simulate(&5, &0);
+    assert_eq!(payouts.len(), 1);
+    assert_eq!(payouts.get_unchecked(0).unwrap(), (user2, 300));
+}
+
+// Three attendees and one no-show: the 200 unclaimed split three ways leaves a remainder of 2
+fn setup_with_remainder(test: &DistributionTest) -> [Identifier; 4] {
+    let no_show = test.new_user();
+    let users = [
+        test.account_id_to_identifier(&test.attendee_users[0]),
+        test.account_id_to_identifier(&test.attendee_users[1]),
+        test.account_id_to_identifier(&test.attendee_users[2]),
+        test.account_id_to_identifier(&no_show),
+    ];
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.approve_deposit(200, no_show);
+    for user in users.iter() {
+        test.deposit(user);
+    }
+    for user in users[..3].iter() {
+        test.attend(user);
+    }
+    users
+}
+
+// Data of the events the distribution contract published under `topics`, in order
+fn published(test: &DistributionTest, topics: Vec<RawVal>) -> Vec<RawVal> {
+    let env = &test.contract.env;
+    let mut found = Vec::new(env);
+    for event in env.events().all().iter() {
+        let (contract_id, event_topics, data) = event.unwrap();
+        if contract_id == test.contract.contract_id && event_topics == topics {
+            found.push_back(data);
+        }
+    }
+    found
+}
+
+#[test]
+fn test_remainder_retained() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+
+    test.withdraw(3, 0);
+    assert_eq!(test.token.balance(&users[0]), 1066);
+    assert_eq!(test.token.balance(&users[1]), 1066);
+    assert_eq!(test.token.balance(&users[2]), 1066);
+    assert_eq!(
+        test.token.balance(&Identifier::Contract(test.contract.contract_id.clone())),
+        2
+    );
+}
+
+#[test]
+fn test_remainder_first_claimants() {
+    let test = DistributionTest::setup();
+    test.contract.with_source_account(&test.token_admin).set_remain(&RemainderPolicy::FirstPaid);
+    let users = setup_with_remainder(&test);
+
+    assert_eq!(test.contract.stats().remainder, RemainderPolicy::FirstPaid);
+    test.withdraw(3, 0);
+    assert_eq!(test.token.balance(&users[0]), 1067);
+    assert_eq!(test.token.balance(&users[1]), 1067);
+    assert_eq!(test.token.balance(&users[2]), 1066);
+    assert_eq!(
+        test.token.balance(&Identifier::Contract(test.contract.contract_id.clone())),
+        0
+    );
+}
+
+#[test]
+fn test_remainder_sweep() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let beneficiary = test.account_id_to_identifier(&test.new_user());
+    admin.set_remain(&RemainderPolicy::Sweep);
+    admin.set_benef(&beneficiary);
+    setup_with_remainder(&test);
+
+    test.withdraw(3, 0);
+    assert_eq!(admin.sweep_rem(), 2);
+    assert_eq!(test.token.balance(&beneficiary), 1002);
+}
+
+#[test]
+#[should_panic(expected = "remainder already swept")]
+fn test_remainder_swept_twice() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    admin.set_remain(&RemainderPolicy::Sweep);
+    admin.set_benef(&test.account_id_to_identifier(&test.attendee_users[0]));
+    setup_with_remainder(&test);
+
+    test.withdraw(3, 0);
+    admin.sweep_rem();
+    admin.sweep_rem();
+}
+
+#[test]
+fn test_stats() {
+    let test = DistributionTest::setup();
+    setup_with_remainder(&test);
+
+    let stats = test.contract.stats();
+    assert_eq!(stats.price, 200);
+    assert_eq!(stats.registered, 4);
+    assert_eq!(stats.attended, 3);
+    assert_eq!(stats.unclaimed, 200);
+    assert_eq!(stats.remainder, RemainderPolicy::Retain);
+}
+
+#[test]
+#[should_panic(expected = "attendee not on allowlist")]
+fn test_deposit_not_on_allowlist() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+
+    admin.set_allow(&true);
+    admin.allow(&test.account_id_to_identifier(&test.attendee_users[1]));
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&test.account_id_to_identifier(&test.attendee_users[0]));
+}
+
+#[test]
+fn test_allowlist() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    admin.set_allow(&true);
+    admin.allow_n(&vec![&test.contract.env, user0.clone(), user1.clone()]);
+    admin.unallow(&user1);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    assert_eq!(test.token.balance(&user0), 800);
+
+    // Once the mode is off anyone can register
+    admin.set_allow(&false);
+    test.deposit(&user1);
+    assert_eq!(test.token.balance(&user1), 800);
+}
+
+#[test]
+#[should_panic(expected = "attendee is blocked")]
+fn test_blocked_deposit() {
+    let test = DistributionTest::setup();
+
+    test.contract.with_source_account(&test.token_admin).block(&test.account_id_to_identifier(&test.attendee_users[0]));
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&test.account_id_to_identifier(&test.attendee_users[0]));
+}
+
+#[test]
+fn test_block_registered_attendee() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+
+    // User1 gets their fee back and no longer counts towards the pool
+    test.contract.with_source_account(&test.token_admin).block(&user1);
+    assert_eq!(test.token.balance(&user1), 1000);
+    assert_eq!(test.contract.stats().registered, 1);
+    assert_eq!(test.contract.stats().unclaimed, 200);
+
+    test.attend(&user0);
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&user0), 1000);
+}
+
+#[test]
+fn test_oracle_priced_deposit() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+    let oracle_id = env.register_contract(None, MockOracle {});
+
+    // 1.5 USD
+    test.contract.with_source_account(&test.token_admin).set_oracle(&oracle_id, &15_000_000);
+    assert_eq!(test.contract.stats().price, 225);
+
+    test.approve_deposit(225, test.attendee_users[0].clone());
+    test.deposit(&user);
+    assert_eq!(test.token.balance(&user), 775);
+    assert_eq!(test.contract.attendee(&user).fee, 225);
+}
+
+#[test]
+fn test_pro_rata_distribution() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+
+    // Price drops to 100 for later registrants
+    let oracle_id = test.contract.env.register_contract(None, MockOracle {});
+    test.contract.with_source_account(&test.token_admin).set_oracle(&oracle_id, &6_666_667);
+    test.deposit(&user1);
+    test.deposit(&user2);
+
+    test.attend(&user0);
+    test.attend(&user1);
+
+    // The whole pot of 400 is split by fee paid
+    test.contract.with_source_account(&test.token_admin).finalize(&DistributionMode::ProRata);
+    test.withdraw(2, 0);
+    assert_eq!(test.token.balance(&user0), 1066);
+    assert_eq!(test.token.balance(&user1), 1033);
+    assert_eq!(test.token.balance(&user2), 900);
+}
+
+#[test]
+#[should_panic(expected = "distribution already started")]
+fn test_deposit_after_finalize() {
+    let test = DistributionTest::setup();
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user1);
+    test.attend(&user1);
+    test.contract.with_source_account(&test.token_admin).finalize(&DistributionMode::InKind);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&test.account_id_to_identifier(&test.attendee_users[0]));
+}
+
+#[test]
+fn test_setters_after_finalize() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    test.attend(&user0);
+    admin.finalize(&DistributionMode::InKind);
+
+    assert!(admin.try_set_inst(&20000).is_err());
+    assert!(admin.try_set_surchg(&0, &500, &false).is_err());
+    assert!(admin.try_set_chkpt(&2).is_err());
+    assert!(admin.try_set_allow(&true).is_err());
+}
+
+#[test]
+#[should_panic(expected = "distribution already started")]
+fn test_attend_after_withdraw() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+    test.withdraw(1, 0);
+
+    test.attend(&user1);
+}
+
+#[test]
+fn test_tier_capacity() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    admin.set_tier(&1, &500, &1);
+    admin.set_tier(&2, &100, &10);
+    assert_eq!(
+        test.contract.tier_avail(),
+        vec![&test.contract.env, (1, 1), (2, 10)]
+    );
+
+    test.approve_deposit(500, test.attendee_users[0].clone());
+    test.approve_deposit(100, test.attendee_users[1].clone());
+    test.contract.dep_tier(&user0, &1);
+    test.contract.dep_tier(&user1, &2);
+
+    assert_eq!(test.token.balance(&user0), 500);
+    assert_eq!(test.token.balance(&user1), 900);
+    assert_eq!(test.contract.attendee(&user0).tier, Some(1));
+    assert_eq!(
+        test.contract.tier_avail(),
+        vec![&test.contract.env, (1, 0), (2, 9)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "tier sold out")]
+fn test_tier_sold_out() {
+    let test = DistributionTest::setup();
+
+    test.contract.with_source_account(&test.token_admin).set_tier(&1, &200, &1);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.contract.dep_tier(&test.account_id_to_identifier(&test.attendee_users[0]), &1);
+    test.contract.dep_tier(&test.account_id_to_identifier(&test.attendee_users[1]), &1);
+}
+
+#[test]
+fn test_quote() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    admin.set_tier(&1, &500, &10);
+    assert_eq!(test.contract.quote(&user, &None), 200);
+    assert_eq!(test.contract.quote(&user, &Some(1)), 500);
+
+    // Only the first installment is charged upfront
+    admin.set_inst(&20000);
+    assert_eq!(test.contract.quote(&user, &Some(1)), 250);
+
+    test.approve_deposit(250, test.attendee_users[0].clone());
+    test.contract.dep_tier(&user, &1);
+    assert_eq!(test.token.balance(&user), 750);
+}
+
+// A new ed25519 user holding 1000 tokens, with their signature on a transfer of `amount`
+// to the contract and its nonce
+fn signed_transfer(test: &DistributionTest, amount: i128) -> (Identifier, Signature, i128) {
+    let env = &test.contract.env;
+    let (user, signer) = soroban_auth::testutils::ed25519::generate(env);
+    test.token.with_source_account(&test.token_admin).mint(&Signature::Invoker, &0, &user, &1000);
+
+    let to = Identifier::Contract(test.contract.contract_id.clone());
+    let nonce = test.token.nonce(&user);
+    let auth = soroban_auth::testutils::ed25519::sign(
+        env,
+        &signer,
+        &test.token.contract_id,
+        symbol!("xfer"),
+        (&user, &nonce, &to, &amount),
+    );
+    (user, auth, nonce)
+}
+
+#[test]
+fn test_register_prepaid() {
+    let test = DistributionTest::setup();
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+    let sender = test.contract.with_source_account(&test.attendee_users[0]);
+
+    // Sent without an allowance, then reported; the surplus comes back on registration
+    test.token.with_source_account(&test.attendee_users[0]).xfer(
+        &Signature::Invoker,
+        &0,
+        &Identifier::Contract(test.contract.contract_id.clone()),
+        &250,
+    );
+    sender.notify_pay(&user, &250);
+    assert_eq!(test.contract.credit(&user), 250);
+    sender.prepaid(&user);
+
+    assert_eq!(test.token.balance(&user), 800);
+    assert_eq!(test.contract.attendee(&user).fee, 200);
+    assert_eq!(test.contract.credit(&user), 0);
+    assert_eq!(test.contract.status().held, 200);
+}
+
+#[test]
+#[should_panic(expected = "payment not received")]
+fn test_register_prepaid_without_payment() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    // User0's credit can't be spent by User1
+    test.token.with_source_account(&test.attendee_users[0]).xfer(
+        &Signature::Invoker,
+        &0,
+        &Identifier::Contract(test.contract.contract_id.clone()),
+        &200,
+    );
+    test.contract.with_source_account(&test.attendee_users[0]).notify_pay(&user0, &200);
+    test.contract.with_source_account(&test.attendee_users[1]).prepaid(&user1);
+}
+
+#[test]
+#[should_panic(expected = "payment not received")]
+fn test_notify_payment_twice() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    // Tokens already credited to User0 can't be reported again by User1
+    test.token.with_source_account(&test.attendee_users[0]).xfer(
+        &Signature::Invoker,
+        &0,
+        &Identifier::Contract(test.contract.contract_id.clone()),
+        &200,
+    );
+    test.contract.with_source_account(&test.attendee_users[0]).notify_pay(&user0, &200);
+    test.contract.with_source_account(&test.attendee_users[1]).notify_pay(&user1, &200);
+}
+
+#[test]
+fn test_deposit_with_transfer() {
+    let test = DistributionTest::setup();
+
+    // The attendee signs the transfer itself, no allowance needed
+    let (user, auth, nonce) = signed_transfer(&test, 200);
+    test.contract.dep_xfer(&user, &auth, &nonce);
+
+    assert_eq!(test.token.balance(&user), 800);
+    assert_eq!(test.contract.attendee(&user).fee, 200);
+    assert_eq!(test.contract.status().held, 200);
+}
+
+#[test]
+fn test_deposit_and_attend_return_indexes() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    assert_eq!(test.contract.deposit(&user0, &None), (0, 200));
+    assert_eq!(test.contract.deposit(&user1, &None), (1, 200));
+
+    let admin = test.contract.with_source_account(&test.token_admin);
+    assert_eq!(admin.attend(&user1, &None), 0);
+    assert_eq!(admin.attend(&user0, &None), 1);
+}
+
+#[test]
+#[should_panic(expected = "contract attendees not allowed")]
+fn test_contract_attendee_disallowed() {
+    let test = DistributionTest::setup();
+    let wrapper = test.contract.env.register_contract(None, MockOracle {});
+
+    test.contract.with_source_account(&test.token_admin).set_ctrs(&false);
+    test.deposit(&Identifier::Contract(wrapper));
+}
+
+#[test]
+fn test_contract_attendee_allowed() {
+    let test = DistributionTest::setup();
+    let wrapper = test.contract.env.register_contract(None, MockOracle {});
+    let wrapper_id = Identifier::Contract(wrapper.clone());
+    test.token.with_source_account(&test.token_admin).mint(&Signature::Invoker, &0, &wrapper_id, &200);
+
+    // The wrapping contract registers itself on behalf of its user
+    test.contract.env.as_contract(&wrapper, || {
+        test.token.incr_allow(
+            &Signature::Invoker,
+            &0,
+            &Identifier::Contract(test.contract.contract_id.clone()),
+            &200,
+        );
+        DistributionContractClient::new(&test.contract.env, &test.contract.contract_id).deposit(&wrapper_id, &None)
+    });
+    assert_eq!(test.contract.attendee(&wrapper_id).fee, 200);
+}
+
+#[test]
+fn test_audit_log() {
+    let test = DistributionTest::setup();
+    let admin_id = test.account_id_to_identifier(&test.token_admin);
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user);
+    test.attend(&user);
+    test.set_timestamp(20000);
+    test.withdraw(1, 0);
+
+    let log = test.contract.audit_log(&10, &0);
+    assert_eq!(log.len(), 2);
+
+    let entry = log.get_unchecked(0).unwrap();
+    assert_eq!(entry.action, symbol!("attend"));
+    assert_eq!(entry.actor, admin_id);
+    assert_eq!(entry.target, Some(user));
+    assert_eq!(entry.timestamp, 12345);
+
+    let entry = log.get_unchecked(1).unwrap();
+    assert_eq!(entry.action, symbol!("withdraw"));
+    assert_eq!(entry.target, None);
+    assert_eq!(entry.timestamp, 20000);
+}
+
+#[test]
+fn test_approved_beneficiary_change() {
+    let test = DistributionTest::setup();
+    let admin_id = test.account_id_to_identifier(&test.token_admin);
+    let approver = test.contract.env.accounts().generate();
+    let approver_id = test.account_id_to_identifier(&approver);
+    let beneficiary = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.contract.with_source_account(&test.token_admin).set_appr(
+        &vec![&test.contract.env, admin_id, approver_id],
+        &2
+    );
+
+    let action_id = test.contract
+        .with_source_account(&test.token_admin)
+        .propose(&AdminAction::SetBenef(beneficiary.clone()));
+    assert!(!test.contract.proposal(&action_id).executed);
+
+    test.contract.with_source_account(&approver).approve(&action_id);
+    assert!(test.contract.proposal(&action_id).executed);
+}
+
+#[test]
+fn test_approved_cancel() {
+    let test = DistributionTest::setup();
+    let admin_id = test.account_id_to_identifier(&test.token_admin);
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let approver = test.contract.env.accounts().generate();
+    let approver_id = test.account_id_to_identifier(&approver);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    admin.set_appr(&vec![&test.contract.env, admin_id, approver_id], &2);
+    assert!(admin.try_cancel().is_err());
+
+    let action_id = admin.propose(&AdminAction::Cancel);
+    test.contract.with_source_account(&approver).approve(&action_id);
+    assert_eq!(test.contract.status().mode, Some(DistributionMode::Refund));
+    admin.refund(&10, &0);
+    assert_eq!(test.token.balance(&user0), 1000);
+}
+
+#[test]
+fn test_timelocked_cancel() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+
+    admin.set_tlock(&3600);
+    assert!(admin.try_cancel().is_err());
+    let action_id = admin.propose(&AdminAction::Cancel);
+    assert_eq!(test.contract.status().mode, None);
+
+    test.set_timestamp(12345 + 3600);
+    admin.execute(&action_id);
+    assert_eq!(test.contract.status().mode, Some(DistributionMode::Refund));
+}
+
+#[test]
+#[should_panic(expected = "action requires approval")]
+fn test_sensitive_action_without_approval() {
+    let test = DistributionTest::setup();
+    let admin_id = test.account_id_to_identifier(&test.token_admin);
+    let admin = test.contract.with_source_account(&test.token_admin);
+
+    admin.set_appr(&vec![&test.contract.env, admin_id.clone()], &1);
+    admin.set_benef(&admin_id);
+}
+
+#[test]
+#[should_panic(expected = "not authorized by approver")]
+fn test_approve_by_non_approver() {
+    let test = DistributionTest::setup();
+    let admin_id = test.account_id_to_identifier(&test.token_admin);
+    let admin = test.contract.with_source_account(&test.token_admin);
+
+    admin.set_appr(&vec![&test.contract.env, admin_id.clone()], &1);
+    test.contract.with_source_account(&test.attendee_users[0]).propose(&AdminAction::Sweep);
+}
+
+#[test]
+fn test_timelocked_action() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let beneficiary = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    admin.set_tlock(&3600);
+    let action_id = admin.propose(&AdminAction::SetBenef(beneficiary));
+
+    let pending = test.contract.pending();
+    assert_eq!(pending.len(), 1);
+    let (pending_id, proposal) = pending.get_unchecked(0).unwrap();
+    assert_eq!(pending_id, action_id);
+    assert_eq!(proposal.ready_at, 12345 + 3600);
+    assert!(!proposal.executed);
+
+    test.set_timestamp(12345 + 3600);
+    admin.execute(&action_id);
+    assert!(test.contract.proposal(&action_id).executed);
+    assert_eq!(test.contract.pending().len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "action not executable yet")]
+fn test_timelocked_action_too_early() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+
+    admin.set_tlock(&3600);
+    let action_id = admin.propose(&AdminAction::SetRemain(RemainderPolicy::Sweep));
+
+    test.set_timestamp(12345 + 3599);
+    admin.execute(&action_id);
+}
+
+#[test]
+fn test_exit_during_timelock() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+
+    admin.set_tlock(&3600);
+    admin.propose(&AdminAction::SetBenef(test.account_id_to_identifier(&test.token_admin)));
+
+    // User0 already attended but can still leave with their deposit
+    test.contract.with_source_account(&test.attendee_users[0]).exit(&user0);
+    assert_eq!(test.token.balance(&user0), 1000);
+
+    let stats = test.contract.stats();
+    assert_eq!(stats.registered, 1);
+    assert_eq!(stats.attended, 0);
+    assert_eq!(stats.unclaimed, 200);
+}
+
+#[test]
+#[should_panic(expected = "no pending action to exit from")]
+fn test_exit_without_pending_action() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    test.contract.with_source_account(&test.attendee_users[0]).exit(&user0);
+}
+
+#[test]
+fn test_attendee_flags_from_legacy() {
+    let att = Attendee::from(LegacyAttendee { fee: 200, attended: true, refunded: false });
+    assert_eq!(att.flags, FLAG_ATTENDED);
+    assert!(att.attended());
+    assert!(!att.refunded());
+
+    let mut att = Attendee::from(LegacyAttendee { fee: 200, attended: true, refunded: true });
+    assert!(att.refunded());
+    att.clear_flag(FLAG_REFUNDED);
+    assert!(!att.refunded());
+    assert!(att.attended());
+}
+
+#[test]
+fn test_migrate_v1_to_v2() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let no_show = test.account_id_to_identifier(&test.attendee_users[2]);
+    let contract = Identifier::Contract(test.contract.contract_id.clone());
+    test.token.with_source_account(&test.token_admin).mint(&Signature::Invoker, &0, &contract, &600);
+
+    // Legacy layout: records under the bare identifier, withdrawal indexes under bare u32 keys
+    env.as_contract(&test.contract.contract_id, || {
+        env.storage().remove(DataKey::Withdraws);
+        env.storage().set(DataKey::Count, 2u32);
+        env.storage().set(DataKey::Unclaimed, 200i128);
+        env.storage().set(0u32, user0.clone());
+        env.storage().set(1u32, user1.clone());
+        env.storage().set(user0.clone(), LegacyAttendee { fee: 200, attended: true, refunded: false });
+        env.storage().set(user1.clone(), LegacyAttendee { fee: 200, attended: true, refunded: false });
+        env.storage().set(no_show.clone(), LegacyAttendee { fee: 200, attended: false, refunded: false });
+    });
+
+    assert_eq!(admin.mig_v1_v2(&0, &1), 1);
+    assert_eq!(admin.mig_v1_v2(&1, &5), 1);
+
+    assert_eq!(test.contract.id_at(&0), Some(user0.clone()));
+    assert_eq!(test.contract.id_at(&1), Some(user1.clone()));
+    assert!(test.contract.attendee(&user0).attended());
+    assert_eq!(test.contract.attendee(&user1).fee, 200);
+    assert_eq!(test.contract.status().held, 600);
+    env.as_contract(&test.contract.contract_id, || {
+        let registered: u32 = env.storage().get_unchecked(DataKey::Registered).unwrap();
+        assert_eq!(registered, 2);
+        assert_eq!(get_attended_fees(env), 400);
+        let version: u32 = env.storage().get_unchecked(DataKey::StorageVer).unwrap();
+        assert_eq!(version, STORAGE_VERSION);
+
+        assert!(!env.storage().has(DataKey::Count));
+        assert!(!env.storage().has(0u32));
+        assert!(!env.storage().has(1u32));
+        assert!(!env.storage().has(user0.clone()));
+        assert!(!env.storage().has(user1.clone()));
+        // Registrants without a withdrawal index are left to `mig_atts`
+        assert!(env.storage().has(no_show.clone()));
+    });
+
+    // The migrated event distributes the no-show's fee as usual
+    assert_eq!(test.withdraw(2, 0), 2);
+    assert_eq!(test.token.balance(&user0), 1300);
+}
+
+#[test]
+fn test_migrate_attendees() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let contract = Identifier::Contract(test.contract.contract_id.clone());
+    test.token.with_source_account(&test.token_admin).mint(&Signature::Invoker, &0, &contract, &200);
+
+    // Registered but not checked in yet, stored under the bare identifier
+    env.as_contract(&test.contract.contract_id, || {
+        env.storage().set(user0.clone(), LegacyAttendee { fee: 200, attended: false, refunded: false });
+        env.storage().set(DataKey::Held, 200i128);
+        env.storage().set(DataKey::Unclaimed, 200i128);
+    });
+
+    // Identifiers without a legacy record are skipped
+    assert_eq!(admin.mig_atts(&vec![env, user0.clone(), user1.clone()]), 1);
+    assert_eq!(admin.mig_atts(&vec![env, user0.clone()]), 0);
+
+    let att = test.contract.attendee(&user0);
+    assert_eq!(att.fee, 200);
+    assert!(!att.attended() && !att.refunded());
+    env.as_contract(&test.contract.contract_id, || {
+        assert!(!env.storage().has(user0.clone()));
+        assert!(env.storage().has(DataKey::AttRec(user0.clone())));
+        let registered: u32 = env.storage().get_unchecked(DataKey::Registered).unwrap();
+        assert_eq!(registered, 1);
+        assert_eq!(get_attended_fees(env), 0);
+    });
+
+    // The migrated registrant checks in and gets the first withdrawal index
+    test.attend(&user0);
+    assert_eq!(test.contract.id_at(&0), Some(user0));
+}
+
+#[test]
+fn test_withdrawals_in_attendance_order() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let users = [
+        test.account_id_to_identifier(&test.attendee_users[0]),
+        test.account_id_to_identifier(&test.attendee_users[1]),
+        test.account_id_to_identifier(&test.attendee_users[2]),
+    ];
+    let no_show = test.new_user();
+    for user in test.attendee_users.iter() {
+        test.approve_deposit(200, user.clone());
+    }
+    test.approve_deposit(200, no_show.clone());
+    for user in users.iter() {
+        test.deposit(user);
+    }
+    test.deposit(&test.account_id_to_identifier(&no_show));
+
+    // Withdrawal indexes follow check-in order, not registration order
+    test.attend(&users[2]);
+    test.attend(&users[0]);
+    test.attend(&users[1]);
+    env.as_contract(&test.contract.contract_id, || {
+        assert_eq!(
+            get_withdrawals(env),
+            vec![env, users[2].clone(), users[0].clone(), users[1].clone()]
+        );
+    });
+
+    // A batch pays exactly the indexes in its range
+    assert_eq!(test.withdraw(2, 1), 1);
+    assert_eq!(test.token.balance(&users[0]), 1066);
+    assert_eq!(test.token.balance(&users[2]), 800);
+
+    let result = test.call_withdraw(3, 0);
+    assert_eq!(result.paid, 2);
+    assert_eq!(result.skipped, 1);
+    assert_eq!(test.token.balance(&users[2]), 1066);
+    assert_eq!(test.token.balance(&users[1]), 1066);
+}
+
+#[test]
+fn test_checkpoint_events() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let fourth = test.new_user();
+    let users = [
+        test.account_id_to_identifier(&test.attendee_users[0]),
+        test.account_id_to_identifier(&test.attendee_users[1]),
+        test.account_id_to_identifier(&test.attendee_users[2]),
+        test.account_id_to_identifier(&fourth),
+    ];
+    for user in test.attendee_users.iter() {
+        test.approve_deposit(200, user.clone());
+    }
+    test.approve_deposit(200, fourth);
+    for user in users.iter() {
+        test.deposit(user);
+    }
+    test.contract.with_source_account(&test.token_admin).set_chkpt(&2);
+    let topics: Vec<RawVal> = (symbol!("checkpoint"),).into_val(env);
+
+    test.attend(&users[0]);
+    assert_eq!(published(&test, topics.clone()).len(), 0);
+    test.attend(&users[1]);
+    assert_eq!(published(&test, topics.clone()), vec![env, (4u32, 2u32).into_val(env)]);
+
+    // Nothing in between intervals
+    test.attend(&users[2]);
+    assert_eq!(published(&test, topics.clone()).len(), 1);
+    test.attend(&users[3]);
+    assert_eq!(
+        published(&test, topics),
+        vec![env, (4u32, 2u32).into_val(env), (4u32, 4u32).into_val(env)]
+    );
+}
+
+#[test]
+fn test_lifecycle_events() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+    test.contract.with_source_account(&test.token_admin).finalize(&DistributionMode::InKind);
+    test.withdraw(1, 0);
+
+    // Topics carry the payload version ahead of the event name
+    assert_eq!(
+        published(&test, (symbol!("v1"), symbol!("deposit")).into_val(env)),
+        vec![
+            env,
+            DepositEvent { attendee: user0.clone(), reg_index: 0, amount: 200 }.into_val(env),
+            DepositEvent { attendee: user1, reg_index: 1, amount: 200 }.into_val(env)
+        ]
+    );
+    assert_eq!(
+        published(&test, EVT_V1_ATTEND.into_val(env)),
+        vec![env, AttendEvent { attendee: user0.clone(), index: 0 }.into_val(env)]
+    );
+    assert_eq!(
+        published(&test, EVT_V1_FINALIZE.into_val(env)),
+        vec![env, FinalizeEvent { mode: DistributionMode::InKind, attended: 1, unclaimed: 200 }.into_val(env)]
+    );
+    assert_eq!(
+        published(&test, (symbol!("v1"), symbol!("payout")).into_val(env)),
+        vec![env, TransferEvent { to: user0.clone(), amount: 400 }.into_val(env)]
+    );
+    assert_eq!(
+        published(&test, (symbol!("v2"), symbol!("payout")).into_val(env)),
+        vec![env, PayoutEvent { to: user0, principal: 200, bonus: 200 }.into_val(env)]
+    );
+}
+
+#[test]
+fn test_version_and_features() {
+    let test = DistributionTest::setup();
+
+    assert_eq!(test.contract.version(), CONTRACT_VERSION);
+    let features = test.contract.features();
+    assert!(features.contains(&symbol!("tiers")));
+    assert!(features.contains(&symbol!("phases")));
+    assert!(features.contains(&symbol!("reserve")));
+    assert!(features.contains(&symbol!("prepaid")));
+    assert!(!features.contains(&symbol!("address")));
+}
+
+#[test]
+fn test_payout_one() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    test.attend(&user0);
+    test.attend(&user2);
+
+    assert_eq!(admin.payout_one(&user2), 300);
+    assert_eq!(test.token.balance(&user2), 1100);
+
+    // The batch skips the attendee that was already paid
+    assert_eq!(test.withdraw(5, 0), 1);
+    assert_eq!(test.token.balance(&user0), 1100);
+    assert_eq!(test.token.balance(&user2), 1100);
+}
+
+#[test]
+fn test_vested_claim() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    admin.set_vest(&1000);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+    admin.finalize(&DistributionMode::InKind);
+
+    let attendee = test.contract.with_source_account(&test.attendee_users[0]);
+    test.set_timestamp(12345 + 250);
+    assert_eq!(attendee.claim(&user0), 100);
+
+    test.set_timestamp(12345 + 500);
+    assert_eq!(attendee.claim(&user0), 100);
+
+    // Fully vested after the period ends
+    test.set_timestamp(12345 + 5000);
+    assert_eq!(attendee.claim(&user0), 200);
+    assert_eq!(test.token.balance(&user0), 1200);
+    assert!(test.contract.attendee(&user0).refunded());
+}
+
+#[test]
+#[should_panic(expected = "payouts are vested")]
+fn test_withdraw_when_vested() {
+    let test = DistributionTest::setup();
+
+    test.contract.with_source_account(&test.token_admin).set_vest(&1000);
+    test.withdraw(5, 0);
+}
+
+#[test]
+fn test_sponsor_match() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let sponsor = test.account_id_to_identifier(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    // 50% match capped at 80
+    admin.set_match(&5000, &80);
+    test.approve_deposit(500, test.token_admin.clone());
+    admin.sponsor(&sponsor, &500);
+    assert_eq!(test.contract.sponsored(&sponsor), 500);
+    assert_eq!(test.contract.stats().sponsored, 500);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    test.attend(&user0);
+    test.attend(&user2);
+
+    test.withdraw(2, 0);
+    assert_eq!(test.token.balance(&user0), 1180);
+    assert_eq!(test.token.balance(&user2), 1180);
+}
+
+#[test]
+#[should_panic(expected = "not authorized by sponsor")]
+fn test_sponsor_by_other() {
+    let test = DistributionTest::setup();
+    let sponsor = test.account_id_to_identifier(&test.token_admin);
+    test.approve_deposit(500, test.token_admin.clone());
+    test.contract.with_source_account(&test.attendee_users[0]).sponsor(&sponsor, &500);
+}
+
+#[test]
+fn test_sponsor_match_underfunded() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    // Full match for two attendees would need 400
+    admin.set_match(&10_000, &1000);
+    test.approve_deposit(100, test.token_admin.clone());
+    admin.sponsor(&test.account_id_to_identifier(&test.token_admin), &100);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+    test.attend(&user1);
+
+    test.withdraw(2, 0);
+    assert_eq!(test.token.balance(&user0), 1050);
+    assert_eq!(test.token.balance(&user1), 1050);
+}
+
+// Three attendees and a no-show, with `prizes` raffle prizes
+fn setup_raffle(test: &DistributionTest, prizes: u32) -> [Identifier; 3] {
+    let no_show = test.new_user();
+    let users = [
+        test.account_id_to_identifier(&test.attendee_users[0]),
+        test.account_id_to_identifier(&test.attendee_users[1]),
+        test.account_id_to_identifier(&test.attendee_users[2]),
+    ];
+
+    test.contract.with_source_account(&test.token_admin).set_prizes(&prizes);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.approve_deposit(200, no_show.clone());
+    for user in users.iter() {
+        test.deposit(user);
+        test.attend(user);
+    }
+    test.deposit(&test.account_id_to_identifier(&no_show));
+    users
+}
+
+#[test]
+fn test_raffle_distribution() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let users = setup_raffle(&test, 1);
+
+    admin.finalize(&DistributionMode::Raffle);
+    let winners = test.contract.winners();
+    assert_eq!(winners.len(), 1);
+    let winner = winners.get_unchecked(0).unwrap();
+
+    // The winner takes the whole forfeited deposit, everyone else gets their fee back
+    test.withdraw(3, 0);
+    for user in users.iter() {
+        if *user == winner {
+            assert_eq!(test.token.balance(user), 1200);
+        } else {
+            assert_eq!(test.token.balance(user), 1000);
+        }
+    }
+}
+
+#[test]
+fn test_raffle_draw_reproducible() {
+    for _ in 0..2 {
+        let test = DistributionTest::setup();
+        let users = setup_raffle(&test, 2);
+        test.set_ledger(50000, 42);
+
+        let expected = test.expected_raffle_winners(2);
+        assert_eq!(expected, vec![&test.contract.env, 1, 0]);
+
+        test.contract.with_source_account(&test.token_admin).finalize(&DistributionMode::Raffle);
+        assert_eq!(test.contract.winners(), vec![&test.contract.env, users[1].clone(), users[0].clone()]);
+    }
+}
+
+#[test]
+fn test_bonus_cap() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let beneficiary = test.account_id_to_identifier(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    admin.set_bonus(&30);
+    admin.set_benef(&beneficiary);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    test.attend(&user0);
+
+    // Only 30 of the 400 forfeited goes to the attendee
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&user0), 1030);
+    assert_eq!(admin.sweep_rem(), 370);
+    assert_eq!(test.token.balance(&beneficiary), 1370);
+}
+
+#[test]
+fn test_feedback_required() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.contract.with_source_account(&test.token_admin).set_fb_req(&true);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+    test.attend(&user1);
+
+    test.contract.with_source_account(&test.attendee_users[1]).feedback(
+        &user1,
+        &BytesN::from_array(&test.contract.env, &[1; 32])
+    );
+
+    // Only User1 submitted feedback
+    assert_eq!(test.withdraw(2, 0), 1);
+    assert_eq!(test.token.balance(&user0), 800);
+    assert_eq!(test.token.balance(&user1), 1000);
+
+    test.contract.with_source_account(&test.attendee_users[0]).feedback(
+        &user0,
+        &BytesN::from_array(&test.contract.env, &[2; 32])
+    );
+    assert_eq!(test.withdraw(2, 0), 1);
+    assert_eq!(test.token.balance(&user0), 1000);
+}
+
+#[test]
+fn test_register_team() {
+    let test = DistributionTest::setup();
+    let captain = test.account_id_to_identifier(&test.attendee_users[0]);
+    let member0 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let member1 = test.account_id_to_identifier(&test.attendee_users[2]);
+    let solo = test.new_user();
+    let solo_id = test.account_id_to_identifier(&solo);
+
+    test.approve_deposit(400, test.attendee_users[0].clone());
+    test.contract.with_source_account(&test.attendee_users[0]).reg_team(
+        &captain,
+        &vec![&test.contract.env, member0.clone(), member1.clone()],
+        &symbol!("hackers")
+    );
+    assert_eq!(test.token.balance(&captain), 600);
+    assert_eq!(test.token.balance(&member0), 1000);
+    assert_eq!(test.contract.get_team(&symbol!("hackers")).captain, captain);
+
+    test.approve_deposit(200, solo);
+    test.deposit(&solo_id);
+
+    // One member attends; their payout is routed to the captain
+    test.attend(&member0);
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&captain), 1200);
+    assert_eq!(test.token.balance(&member0), 1000);
+    assert_eq!(test.token.balance(&solo_id), 800);
+}
+
+#[test]
+#[should_panic(expected = "not authorized by captain")]
+fn test_register_team_by_non_captain() {
+    let test = DistributionTest::setup();
+    let captain = test.account_id_to_identifier(&test.attendee_users[0]);
+    let member = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.contract.with_source_account(&test.attendee_users[1]).reg_team(
+        &captain,
+        &vec![&test.contract.env, member],
+        &symbol!("hackers")
+    );
+}
+
+#[test]
+fn test_comped_attendee() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let speaker = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    admin.comp(&speaker);
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user1);
+    test.deposit(&user2);
+
+    assert_eq!(admin.attend(&speaker, &None), NO_WITHDRAWAL_INDEX);
+    assert_eq!(admin.attend(&user1, &None), 0);
+
+    let stats = test.contract.stats();
+    assert_eq!(stats.registered, 3);
+    assert_eq!(stats.attended, 2);
+    assert_eq!(stats.comped, 1);
+
+    // The speaker doesn't dilute the pool
+    test.withdraw(5, 0);
+    assert_eq!(test.token.balance(&user1), 1200);
+    assert_eq!(test.token.balance(&speaker), 1000);
+}
+
+#[test]
+#[should_panic(expected = "comped attendees are not paid out")]
+fn test_comped_payout_one() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let speaker = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    admin.comp(&speaker);
+    admin.attend(&speaker, &None);
+    admin.finalize(&DistributionMode::InKind);
+    admin.payout_one(&speaker);
+}
+
+#[test]
+fn test_late_surcharge() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    admin.set_surchg(&20000, &2500, &false);
+    test.approve_deposit(250, test.attendee_users[0].clone());
+    test.approve_deposit(250, test.attendee_users[1].clone());
+
+    assert_eq!(test.contract.deposit(&user0, &None), (0, 200));
+
+    // 25% more after the surcharge window opens, all of it refundable
+    test.set_timestamp(20000);
+    assert_eq!(test.contract.quote(&user1, &None), 250);
+    assert_eq!(test.contract.deposit(&user1, &None), (1, 250));
+    assert_eq!(test.contract.attendee(&user1).fee, 250);
+}
+
+#[test]
+fn test_late_surcharge_to_beneficiary() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let beneficiary = test.account_id_to_identifier(&test.attendee_users[2]);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    admin.set_benef(&beneficiary);
+    admin.set_surchg(&0, &2500, &true);
+    test.approve_deposit(250, test.attendee_users[0].clone());
+
+    assert_eq!(test.contract.deposit(&user0, &None), (0, 250));
+    assert_eq!(test.token.balance(&user0), 750);
+    assert_eq!(test.token.balance(&beneficiary), 1050);
+    assert_eq!(test.contract.attendee(&user0).fee, 200);
+}
+
+#[test]
+fn test_surcharge_to_beneficiary_with_transfer() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let beneficiary = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    admin.set_benef(&beneficiary);
+    admin.set_surchg(&0, &2500, &true);
+
+    // The surcharge comes out of the signed transfer, no allowance needed
+    let (user, auth, nonce) = signed_transfer(&test, 250);
+    assert_eq!(test.contract.dep_xfer(&user, &auth, &nonce), (0, 250));
+    assert_eq!(test.token.balance(&user), 750);
+    assert_eq!(test.token.balance(&beneficiary), 1050);
+    assert_eq!(test.contract.status().held, 200);
+}
+
+#[test]
+fn test_withdraw_batch_result() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    test.attend(&user0);
+    test.attend(&user2);
+
+    assert_eq!(
+        test.call_withdraw(1, 0),
+        BatchResult { paid: 1, skipped: 0, total: 300, remaining: 1 }
+    );
+    assert_eq!(
+        test.call_withdraw(5, 0),
+        BatchResult { paid: 1, skipped: 1, total: 300, remaining: 0 }
+    );
+}
+
+#[test]
+fn test_free_event() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    admin.set_tier(&1, &0, &10);
+    test.contract.dep_tier(&user0, &1);
+    test.contract.dep_tier(&user1, &1);
+    test.attend(&user0);
+
+    assert_eq!(
+        test.call_withdraw(5, 0),
+        BatchResult { paid: 1, skipped: 0, total: 0, remaining: 0 }
+    );
+    assert_eq!(test.token.balance(&user0), 1000);
+    assert_eq!(test.token.balance(&user1), 1000);
+}
+
+#[test]
+fn test_pro_rata_free_attendees() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    admin.set_tier(&1, &0, &10);
+    test.contract.dep_tier(&user0, &1);
+    test.contract.dep_tier(&user2, &1);
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user1);
+    test.attend(&user0);
+    test.attend(&user2);
+
+    // No attended fees to weigh by, so the no-show's deposit is split evenly
+    admin.finalize(&DistributionMode::ProRata);
+    test.withdraw(5, 0);
+    assert_eq!(test.token.balance(&user0), 1100);
+    assert_eq!(test.token.balance(&user2), 1100);
+}
+
+#[test]
+fn test_withdraw_nobody_attended() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+
+    assert_eq!(
+        test.call_withdraw(5, 0),
+        BatchResult { paid: 0, skipped: 0, total: 0, remaining: 0 }
+    );
+    assert_eq!(test.contract.simulate(&5, &0).len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "negative price")]
+fn test_negative_tier_price() {
+    let test = DistributionTest::setup();
+    test.contract.with_source_account(&test.token_admin).set_tier(&1, &-1, &10);
+}
+
+#[test]
+#[should_panic(expected = "amount overflow")]
+fn test_payout_math_overflow() {
+    mul_div(i128::MAX / 2, 3, 10_000);
+}
+
+#[test]
+fn test_deposit_split() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let employer = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    // The employer covers 75% of the deposit
+    test.approve_deposit(50, test.attendee_users[0].clone());
+    test.approve_deposit(150, test.attendee_users[1].clone());
+    test.contract
+        .with_source_account(&test.attendee_users[1])
+        .dep_split(&user0, &employer, &7_500);
+    assert_eq!(test.token.balance(&user0), 950);
+    assert_eq!(test.token.balance(&employer), 850);
+
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user2);
+    test.attend(&user0);
+
+    // The payout of 400 is split back the same way
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&user0), 1050);
+    assert_eq!(test.token.balance(&employer), 1150);
+}
+
+#[test]
+#[should_panic(expected = "Invalid share")]
+fn test_deposit_split_over_full() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let employer = test.account_id_to_identifier(&test.attendee_users[1]);
+    test.contract
+        .with_source_account(&test.attendee_users[1])
+        .dep_split(&user0, &employer, &10_001);
+}
+
+#[test]
+#[should_panic(expected = "not authorized by payer")]
+fn test_deposit_split_by_non_payer() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let employer = test.account_id_to_identifier(&test.attendee_users[1]);
+    test.approve_deposit(50, test.attendee_users[0].clone());
+    test.approve_deposit(150, test.attendee_users[1].clone());
+    test.contract
+        .with_source_account(&test.attendee_users[0])
+        .dep_split(&user0, &employer, &7_500);
+}
+
+#[test]
+fn test_export_state() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    test.attend(&user1);
+
+    let first = test.contract.export(&0, &2);
+    assert_eq!(first.stats.registered, 3);
+    assert_eq!(first.token, test.token.contract_id);
+    assert_eq!(first.mode, None);
+    assert_eq!(first.attendees.len(), 2);
+    assert_eq!(first.next, 2);
+    assert_eq!(first.total, 3);
+    let (id, att) = first.attendees.get_unchecked(1).unwrap();
+    assert_eq!(id, user1);
+    assert!(att.attended());
+
+    let second = test.contract.export(&first.next, &2);
+    assert_eq!(second.attendees.len(), 1);
+    assert_eq!(second.next, second.total);
+}
+
+#[test]
+fn test_bind_ticket() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let ticket = BytesN::from_array(&test.contract.env, &[3; 32]);
+    let new_ticket = BytesN::from_array(&test.contract.env, &[4; 32]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    test.contract.with_source_account(&test.attendee_users[0]).tie_ticket(&user0, &ticket);
+    assert_eq!(test.contract.by_ticket(&ticket), user0);
+
+    // Reissued ticket replaces the old one
+    test.contract.with_source_account(&test.token_admin).tie_ticket(&user0, &new_ticket);
+    assert_eq!(test.contract.by_ticket(&new_ticket), user0);
+}
+
+#[test]
+#[should_panic(expected = "not authorized by attendee")]
+fn test_bind_ticket_by_other() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let ticket = BytesN::from_array(&test.contract.env, &[3; 32]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    test.contract.with_source_account(&test.attendee_users[1]).tie_ticket(&user0, &ticket);
+}
+
+#[test]
+fn test_dispute_accepted() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let evidence = BytesN::from_array(&test.contract.env, &[5; 32]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+
+    // The missed scan is contested and upheld
+    test.contract.with_source_account(&test.attendee_users[1]).dispute(&user1, &evidence.clone());
+    admin.resolve(&user1, &true);
+
+    let att = test.contract.attendee(&user1);
+    assert!(att.attended());
+    assert!(!att.disputed());
+    assert_eq!(att.evidence, Some(evidence));
+
+    test.withdraw(2, 0);
+    assert_eq!(test.token.balance(&user0), 1000);
+    assert_eq!(test.token.balance(&user1), 1000);
+}
+
+#[test]
+#[should_panic(expected = "disputes pending")]
+fn test_dispute_blocks_distribution() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let evidence = BytesN::from_array(&test.contract.env, &[5; 32]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+
+    test.contract.with_source_account(&test.attendee_users[1]).dispute(&user1, &evidence);
+    test.withdraw(2, 0);
+}
+
+// Contract sharing the token of `test` with disputes handled by `arbiter`
+fn create_arbitrated_contract(test: &DistributionTest, arbiter: &AccountId) -> DistributionContractClient {
+    let env = &test.contract.env;
+    let distr = DistributionContractClient::new(env, env.register_contract(None, DistributionContract {}));
+    let mut config = InitConfig::new(
+        test.account_id_to_identifier(&test.token_admin),
+        200,
+        test.token.contract_id.clone()
+    );
+    config.arbiter = Some(test.account_id_to_identifier(arbiter));
+    distr.initialize(&config);
+    distr
+}
+
+#[test]
+fn test_arbiter_resolves_dispute() {
+    let test = DistributionTest::setup();
+    let arbiter = test.new_user();
+    let contract = create_arbitrated_contract(&test, &arbiter);
+    let speaker = test.account_id_to_identifier(&test.attendee_users[0]);
+    let evidence = BytesN::from_array(&test.contract.env, &[5; 32]);
+
+    contract.with_source_account(&test.token_admin).comp(&speaker);
+    contract.with_source_account(&test.attendee_users[0]).dispute(&speaker, &evidence);
+    contract.with_source_account(&arbiter).resolve(&speaker, &false);
+    assert!(!contract.attendee(&speaker).disputed());
+}
+
+#[test]
+#[should_panic(expected = "not authorized by arbiter")]
+fn test_admin_cannot_resolve_with_arbiter() {
+    let test = DistributionTest::setup();
+    let arbiter = test.new_user();
+    let contract = create_arbitrated_contract(&test, &arbiter);
+    let speaker = test.account_id_to_identifier(&test.attendee_users[0]);
+    let evidence = BytesN::from_array(&test.contract.env, &[5; 32]);
+
+    contract.with_source_account(&test.token_admin).comp(&speaker);
+    contract.with_source_account(&test.attendee_users[0]).dispute(&speaker, &evidence);
+    contract.with_source_account(&test.token_admin).resolve(&speaker, &true);
+}
+
+#[test]
+fn test_quorum_not_met_refunds_everyone() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    admin.set_quorum(&50);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    test.attend(&user0);
+
+    // One in three attended, so finalizing cancels the event
+    admin.finalize(&DistributionMode::InKind);
+    assert_eq!(
+        admin.refund(&2, &0),
+        BatchResult { paid: 2, skipped: 0, total: 400, remaining: 1 }
+    );
+    assert_eq!(
+        admin.refund(&10, &0),
+        BatchResult { paid: 1, skipped: 2, total: 200, remaining: 0 }
+    );
+    assert_eq!(test.token.balance(&user0), 1000);
+    assert_eq!(test.token.balance(&user1), 1000);
+    assert_eq!(test.token.balance(&user2), 1000);
+}
+
+#[test]
+#[should_panic(expected = "event not cancelled")]
+fn test_refund_without_cancel() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    admin.set_quorum(&50);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    test.attend(&user0);
+    admin.refund(&10, &0);
+}
+
+#[test]
+fn test_cancel() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    admin.cancel();
+    admin.refund(&10, &0);
+    assert_eq!(test.token.balance(&user0), 1000);
+}
+
+#[test]
+fn test_cancel_returns_sponsorship() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let admin_id = test.account_id_to_identifier(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    admin.set_benef(&admin_id);
+    test.approve_deposit(300, test.token_admin.clone());
+    admin.sponsor(&admin_id, &300);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    admin.cancel();
+    admin.refund(&10, &0);
+
+    assert_eq!(admin.reclaim(&admin_id), 300);
+    assert_eq!(test.token.balance(&admin_id), 1000);
+    assert_eq!(test.contract.stats().sponsored, 0);
+
+    // Nothing is owed anymore, so the contract can be emptied and closed
+    assert_eq!(admin.sweep_rem(), 0);
+    assert_eq!(test.contract.status().held, 0);
+    admin.close(&10);
+}
+
+#[test]
+fn test_yield_added_to_pool() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    let pool_id = test.contract.env.register_contract(None, MockPool {});
+    let pool = MockPoolClient::new(&test.contract.env, &pool_id);
+    pool.init(&test.token.contract_id);
+    // Reserves the pool pays the yield from
+    test.token.with_source_account(&test.token_admin).mint(
+        &Signature::Invoker,
+        &0,
+        &Identifier::Contract(pool_id.clone()),
+        &1000,
+    );
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    admin.set_pool(&pool_id);
+    admin.supply(&400);
+    test.attend(&user0);
+
+    // 40 of yield joins the no-show's deposit
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&user0), 1240);
+}
+
+#[test]
+fn test_frozen_attendee_skipped() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+    test.attend(&user1);
+
+    admin.freeze(&user0);
+    assert_eq!(
+        test.call_withdraw(2, 0),
+        BatchResult { paid: 1, skipped: 1, total: 200, remaining: 1 }
+    );
+    assert_eq!(test.token.balance(&user0), 800);
+
+    admin.unfreeze(&user0);
+    test.withdraw(2, 0);
+    assert_eq!(test.token.balance(&user0), 1000);
+}
+
+#[test]
+#[should_panic(expected = "attendee frozen")]
+fn test_frozen_payout_one() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    test.attend(&user0);
+    admin.freeze(&user0);
+    admin.payout_one(&user0);
+}
+
+#[test]
+fn test_beneficiary_splits() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let host = test.account_id_to_identifier(&test.new_user());
+    let cohost = test.account_id_to_identifier(&test.new_user());
+
+    admin.set_bonus(&0);
+    admin.set_splits(&vec![&test.contract.env, (host.clone(), 7_000), (cohost.clone(), 3_000)]);
+    setup_with_remainder(&test);
+
+    test.withdraw(3, 0);
+    assert_eq!(admin.sweep_rem(), 200);
+    assert_eq!(test.token.balance(&host), 1000);
+
+    assert_eq!(test.contract.pay_fees(), 200);
+    assert_eq!(test.token.balance(&host), 1140);
+    assert_eq!(test.token.balance(&cohost), 1060);
+}
+
+#[test]
+#[should_panic(expected = "Invalid split")]
+fn test_beneficiary_splits_must_add_up() {
+    let test = DistributionTest::setup();
+    let host = test.account_id_to_identifier(&test.new_user());
+    test.contract
+        .with_source_account(&test.token_admin)
+        .set_splits(&vec![&test.contract.env, (host, 9_000)]);
+}
+
+#[test]
+fn test_close() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    test.attend(&user0);
+    test.withdraw(1, 0);
+
+    // One registration, three audit log entries (attend, withdraw, close), two ledger
+    // entries (deposit, payout) and the seat count kept for the attendee
+    assert_eq!(admin.close(&2), 5);
+    assert_eq!(admin.close(&10), 0);
+    assert_eq!(test.contract.audit_log(&10, &0).len(), 0);
+    let env = &test.contract.env;
+    assert!(!env.as_contract(&test.contract.contract_id, || env.storage().has(DataKey::Seats(user0.clone()))));
+}
+
+#[test]
+#[should_panic(expected = "balance not swept")]
+fn test_close_with_balance() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    setup_with_remainder(&test);
+    test.withdraw(3, 0);
+    admin.close(&10);
+}
+
+#[test]
+fn test_pending_claims() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+
+    assert_eq!(test.contract.claims(&0, &10).len(), 3);
+    test.withdraw(2, 0);
+    assert_eq!(
+        test.contract.claims(&0, &10),
+        vec![&test.contract.env, users[2].clone()]
+    );
+    assert_eq!(test.contract.claims(&3, &10).len(), 0);
+}
+
+#[test]
+fn test_payout_order_by_registration() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    admin.set_order(&PayoutOrder::Register);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    test.attend(&user2);
+    test.attend(&user0);
+
+    admin.finalize(&DistributionMode::InKind);
+    assert_eq!(test.contract.claims(&0, &10), vec![&test.contract.env, user0.clone(), user2.clone()]);
+
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&user0), 1100);
+    assert_eq!(test.token.balance(&user2), 800);
+}
+
+#[test]
+fn test_register_again_after_exit() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(400, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    admin.set_tlock(&3600);
+    admin.propose(&AdminAction::SetBenef(test.account_id_to_identifier(&test.token_admin)));
+    test.contract.with_source_account(&test.attendee_users[0]).exit(&user0);
+
+    // A fresh record with a new registration index
+    assert_eq!(test.contract.deposit(&user0, &None), (1, 200));
+    assert_eq!(test.contract.attendee(&user0).reg_index, 1);
+    assert_eq!(test.contract.stats().registered, 1);
+    assert_eq!(test.contract.export(&0, &10).attendees.len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "registration cap reached")]
+fn test_registration_cap() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    assert_eq!(test.contract.max_regs(), MAX_REGISTRATIONS);
+
+    test.contract.env.as_contract(&test.contract.contract_id, || {
+        test.contract.env.storage().set(DataKey::Registered, MAX_REGISTRATIONS);
+    });
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+}
+
+#[test]
+fn test_top_up() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(250, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.contract.with_source_account(&test.attendee_users[0]).top_up(&user0, &50);
+    assert_eq!(test.contract.attendee(&user0).fee, 250);
+    assert_eq!(test.contract.stats().unclaimed, 450);
+
+    // The no-show forfeits the topped up deposit too
+    test.attend(&user1);
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&user1), 1250);
+}
+
+#[test]
+fn test_change_tier() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    admin.set_tier(&1, &100, &10);
+    admin.set_tier(&2, &300, &1);
+    test.approve_deposit(300, test.attendee_users[0].clone());
+    test.contract.dep_tier(&user0, &1);
+
+    let attendee = test.contract.with_source_account(&test.attendee_users[0]);
+    assert_eq!(attendee.move_tier(&user0, &2), 200);
+    assert_eq!(test.token.balance(&user0), 700);
+    assert_eq!(test.contract.get_tier(&2).sold, 1);
+    assert_eq!(test.contract.stats().unclaimed, 300);
+
+    assert_eq!(attendee.move_tier(&user0, &1), -200);
+    assert_eq!(test.token.balance(&user0), 900);
+    assert_eq!(test.contract.get_tier(&2).sold, 0);
+    assert_eq!(test.contract.attendee(&user0).fee, 100);
+}
+
+#[test]
+fn test_purge_settled() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let users = setup_with_remainder(&test);
+
+    test.withdraw(2, 0);
+    assert_eq!(admin.purge(&0, &10), 2);
+    assert_eq!(test.contract.export(&0, &10).attendees.len(), 2);
+
+    let stats = test.contract.stats();
+    assert_eq!(stats.purged, 2);
+    assert_eq!(stats.registered, 4);
+
+    // Purged entries are passed over by later batches
+    assert_eq!(
+        test.call_withdraw(3, 0),
+        BatchResult { paid: 1, skipped: 2, total: 266, remaining: 0 }
+    );
+    assert_eq!(test.token.balance(&users[2]), 1066);
+}
+
+#[cfg(feature = "client")]
+#[test]
+fn test_client_drives_distribution() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let total = client::drive_distribution_to_completion(&admin);
+    assert_eq!(total, BatchResult { paid: 3, skipped: 0, total: 798, remaining: 0 });
+    assert_eq!(test.token.balance(&users[0]), 1066);
+}
+
+#[test]
+fn test_status() {
+    let test = DistributionTest::setup();
+    setup_with_remainder(&test);
+
+    let status = test.contract.status();
+    assert_eq!(status.phase, Phase::Register);
+    assert_eq!(status.mode, None);
+    assert_eq!(status.registered, 4);
+    assert_eq!(status.attended, 3);
+    assert_eq!(status.pool, 200);
+    assert_eq!(status.held, 800);
+
+    test.withdraw(1, 0);
+    let status = test.contract.status();
+    assert_eq!(status.phase, Phase::Paying);
+    assert_eq!(status.mode, Some(DistributionMode::InKind));
+    assert_eq!(status.final_at, Some(12345));
+    assert_eq!(status.paid, 1);
+
+    test.withdraw(3, 1);
+    assert_eq!(test.contract.status().phase, Phase::Settled);
+}
+
+#[test]
+fn test_attend_by_index() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+
+    assert_eq!(admin.attend_idx(&1), 0);
+    assert!(test.contract.attendee(&user1).attended());
+    assert!(!test.contract.attendee(&user0).attended());
+}
+
+#[test]
+#[should_panic(expected = "attendee did not register")]
+fn test_attend_by_unknown_index() {
+    let test = DistributionTest::setup();
+    test.contract.with_source_account(&test.token_admin).attend_idx(&0);
+}
+
+#[test]
+fn test_ledger_entries() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+    test.withdraw(1, 0);
+
+    let entries = test.contract.ledger(&10, &0);
+    assert_eq!(entries.len(), 5);
+    let deposit = entries.get_unchecked(3).unwrap();
+    assert_eq!(deposit.kind, symbol!("deposit"));
+    assert_eq!(deposit.account, users[3]);
+    assert_eq!(deposit.amount, 200);
+    assert_eq!(deposit.balance, 800);
+
+    let payout = entries.get_unchecked(4).unwrap();
+    assert_eq!(payout.kind, symbol!("payout"));
+    assert_eq!(payout.account, users[0]);
+    assert_eq!(payout.amount, -266);
+    assert_eq!(
+        payout.balance,
+        test.token.balance(&Identifier::Contract(test.contract.contract_id.clone()))
+    );
+}
+
+// Registers an ed25519 key as a comped attendee, as such keys cannot approve a deposit here
+fn comp_ed25519_attendee(test: &DistributionTest, seed: u8) -> (Identifier, Keypair) {
+    let secret = SecretKey::from_bytes(&[seed; 32]).unwrap();
+    let public = PublicKey::from(&secret);
+    let keypair = Keypair { secret, public };
+    let id = Identifier::Ed25519(BytesN::from_array(&test.contract.env, &keypair.public.to_bytes()));
+    test.contract.with_source_account(&test.token_admin).comp(&id);
+    (id, keypair)
+}
+
+#[test]
+fn test_attend_with_nonce() {
+    let test = DistributionTest::setup();
+    let (id, keypair) = comp_ed25519_attendee(&test, 7);
+
+    let nonce = test.contract.with_source_account(&test.token_admin).open_nonce();
+    let sig = keypair.sign(&nonce.to_array()).to_bytes();
+    test.contract.attend_own(&id, &BytesN::from_array(&test.contract.env, &sig));
+    assert!(test.contract.attendee(&id).attended());
+}
+
+#[test]
+#[should_panic(expected = "check-in nonce expired")]
+fn test_attend_with_expired_nonce() {
+    let test = DistributionTest::setup();
+    let (id, keypair) = comp_ed25519_attendee(&test, 7);
+
+    let nonce = test.contract.with_source_account(&test.token_admin).open_nonce();
+    let sig = keypair.sign(&nonce.to_array()).to_bytes();
+    test.set_timestamp(12345 + NONCE_TTL + 1);
+    test.contract.attend_own(&id, &BytesN::from_array(&test.contract.env, &sig));
+}
+
+#[test]
+fn test_overbooked_standby_refund() {
+    let test = DistributionTest::setup();
+    test.contract.with_source_account(&test.token_admin).set_cap(&1, &200);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+
+    let admin = test.contract.with_source_account(&test.token_admin);
+    assert_eq!(admin.attend(&user0, &None), 0);
+    assert_eq!(admin.attend(&user1, &None), NO_WITHDRAWAL_INDEX);
+    assert_eq!(test.token.balance(&user1), 1000);
+    assert_eq!(test.contract.stats().registered, 1);
+    assert_eq!(test.contract.stats().attended, 1);
+}
+
+#[test]
+#[should_panic(expected = "event full")]
+fn test_overbooking_limit() {
+    let test = DistributionTest::setup();
+    test.contract.with_source_account(&test.token_admin).set_cap(&1, &100);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+}
+
+#[test]
+fn test_processed_ranges() {
+    let test = DistributionTest::setup();
+    setup_with_remainder(&test);
+
+    test.withdraw(1, 0);
+    test.withdraw(3, 2);
+    assert_eq!(test.contract.processed(), vec![&test.contract.env, (0, 1), (2, 3)]);
+
+    // Retrying the whole range only pays the gap
+    assert_eq!(
+        test.call_withdraw(3, 0),
+        BatchResult { paid: 1, skipped: 2, total: 266, remaining: 0 }
+    );
+    assert_eq!(test.contract.processed(), vec![&test.contract.env, (0, 3)]);
+}
+
+#[test]
+fn test_automation_key_withdraws() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+    let bot = test.new_user();
+    test.contract
+        .with_source_account(&test.token_admin)
+        .set_auto(&Identifier::Account(bot.clone()));
+    test.contract.with_source_account(&test.token_admin).finalize(&DistributionMode::InKind);
+
+    let result = test.contract.with_source_account(&bot).withdraw(&3, &0);
+    assert_eq!(result.paid, 3);
+    assert_eq!(test.token.balance(&users[0]), 1066);
+}
+
+#[test]
+#[should_panic(expected = "event not finalized")]
+fn test_automation_key_cannot_finalize() {
+    let test = DistributionTest::setup();
+    setup_with_remainder(&test);
+    let bot = test.new_user();
+    test.contract
+        .with_source_account(&test.token_admin)
+        .set_auto(&Identifier::Account(bot.clone()));
+
+    test.contract.with_source_account(&bot).withdraw(&3, &0);
+}
+
+#[test]
+#[should_panic(expected = "not authorized by admin")]
+fn test_automation_key_cannot_configure() {
+    let test = DistributionTest::setup();
+    let bot = test.new_user();
+    test.contract
+        .with_source_account(&test.token_admin)
+        .set_auto(&Identifier::Account(bot.clone()));
+
+    test.contract.with_source_account(&bot).set_quorum(&50);
+}
+
+#[test]
+fn test_donate_payout() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+    let charity = test.account_id_to_identifier(&test.new_user());
+    test.contract.with_source_account(&test.token_admin).add_donee(&charity);
+    test.contract.with_source_account(&test.token_admin).finalize(&DistributionMode::InKind);
+
+    let donated = test.contract
+        .with_source_account(&test.attendee_users[0])
+        .donate(&users[0], &charity);
+    assert_eq!(donated, 266);
+    assert_eq!(test.token.balance(&charity), 1266);
+    assert_eq!(test.token.balance(&users[0]), 800);
+    assert_eq!(test.contract.stats().donated, 266);
+
+    // Already settled, so the batch skips the donor
+    assert_eq!(test.call_withdraw(3, 0).paid, 2);
+}
+
+#[test]
+fn test_donate_payout_while_vesting() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let charity = test.account_id_to_identifier(&test.new_user());
+
+    admin.add_donee(&charity);
+    admin.set_vest(&1000);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+    admin.finalize(&DistributionMode::InKind);
+
+    // Only the vested half of the 400 payout can be given away
+    let attendee = test.contract.with_source_account(&test.attendee_users[0]);
+    test.set_timestamp(12345 + 500);
+    assert_eq!(attendee.donate(&user0, &charity), 200);
+    assert_eq!(test.token.balance(&charity), 1200);
+    assert_eq!(test.contract.attendee(&user0).claimed, 200);
+    assert!(!test.contract.attendee(&user0).refunded());
+
+    test.set_timestamp(12345 + 1000);
+    assert_eq!(attendee.donate(&user0, &charity), 200);
+    assert_eq!(test.contract.stats().donated, 400);
+    assert!(test.contract.attendee(&user0).refunded());
+}
+
+#[test]
+fn test_claim_swapped() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+    let env = &test.contract.env;
+
+    let swap_id = env.register_contract(None, MockSwap {});
+    let (usd_id, usd) = create_token_contract(env, &test.token_admin);
+    usd.with_source_account(&test.token_admin).mint(
+        &Signature::Invoker,
+        &0,
+        &Identifier::Contract(swap_id.clone()),
+        &1000,
+    );
+    test.contract.with_source_account(&test.token_admin).set_swap(&swap_id);
+    test.contract.with_source_account(&test.token_admin).finalize(&DistributionMode::InKind);
+
+    let bought = test.contract
+        .with_source_account(&test.attendee_users[0])
+        .claim_swap(&users[0], &usd_id, &100);
+    assert_eq!(bought, 133);
+    assert_eq!(usd.balance(&users[0]), 133);
+    assert_eq!(test.token.balance(&users[0]), 800);
+    assert_eq!(test.token.balance(&Identifier::Contract(swap_id)), 266);
+}
+
+#[test]
+fn test_claim_swapped_while_vesting() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let env = &test.contract.env;
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    let swap_id = env.register_contract(None, MockSwap {});
+    let (usd_id, usd) = create_token_contract(env, &test.token_admin);
+    usd.with_source_account(&test.token_admin).mint(
+        &Signature::Invoker,
+        &0,
+        &Identifier::Contract(swap_id.clone()),
+        &1000,
+    );
+    admin.set_swap(&swap_id);
+    admin.set_vest(&1000);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+    admin.finalize(&DistributionMode::InKind);
+
+    // A quarter of the 400 payout has vested, so only 100 is sold
+    let attendee = test.contract.with_source_account(&test.attendee_users[0]);
+    test.set_timestamp(12345 + 250);
+    assert_eq!(attendee.claim_swap(&user0, &usd_id, &0), 50);
+    assert_eq!(test.token.balance(&Identifier::Contract(swap_id)), 100);
+    assert_eq!(test.contract.attendee(&user0).claimed, 100);
+    assert!(!test.contract.attendee(&user0).refunded());
+
+    // The rest vests as usual
+    test.set_timestamp(12345 + 1000);
+    assert_eq!(attendee.claim(&user0), 300);
+    assert_eq!(test.token.balance(&user0), 1100);
+    assert!(test.contract.attendee(&user0).refunded());
+}
+
+#[test]
+#[should_panic(expected = "event not finalized")]
+fn test_donate_payout_before_finalize() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+    let charity = test.account_id_to_identifier(&test.new_user());
+    test.contract.with_source_account(&test.token_admin).add_donee(&charity);
+
+    test.contract
+        .with_source_account(&test.attendee_users[0])
+        .donate(&users[0], &charity);
+}
+
+#[test]
+fn test_commit_state() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user);
+    test.attend(&user);
+    test.contract.with_source_account(&test.token_admin).finalize(&DistributionMode::InKind);
+    assert_eq!(test.contract.state_root(), None);
+
+    let root = test.contract.commit(&10).unwrap();
+    assert_eq!(test.contract.state_root(), Some(root.clone()));
+
+    // A single leaf hashed up through the zero-padded levels
+    let hash = |left: &BytesN<32>, right: &BytesN<32>| {
+        let mut data: Bytes = left.clone().into();
+        data.append(&right.clone().into());
+        env.crypto().sha256(&data)
+    };
+    let mut node = env.crypto().sha256(&env.serialize_to_bytes((user, 200 as i128, true, 200 as i128)));
+    let mut zero = BytesN::from_array(env, &[0; 32]);
+    node = hash(&node, &zero);
+    for _ in 1..14 {
+        zero = hash(&zero, &zero);
+        node = hash(&node, &zero);
+    }
+    assert_eq!(root, node);
+}
+
+#[test]
+fn test_attend_signed_batch() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let (id0, keypair0) = comp_ed25519_attendee(&test, 7);
+    let (id1, keypair1) = comp_ed25519_attendee(&test, 8);
+
+    let contract_id = test.contract.contract_id.to_array();
+    let sig0 = BytesN::from_array(env, &keypair0.sign(&contract_id).to_bytes());
+    let sig1 = BytesN::from_array(env, &keypair1.sign(&contract_id).to_bytes());
+    test.contract
+        .with_source_account(&test.token_admin)
+        .attend_sig(&vec![env, (id0.clone(), sig0), (id1.clone(), sig1)]);
+
+    assert!(test.contract.attendee(&id0).attended());
+    assert!(test.contract.attendee(&id1).attended());
+}
+
+#[test]
+fn test_rsvp_mode_shares_sponsor_pool() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let admin_id = Identifier::Account(test.token_admin.clone());
+    let rsvp = DistributionContractClient::new(env, env.register_contract(None, DistributionContract {}));
+    rsvp.initialize(&InitConfig::new(admin_id.clone(), 0, test.token.contract_id.clone()));
+    let admin = rsvp.with_source_account(&test.token_admin);
+    admin.set_rsvp(&true);
+
+    test.token.with_source_account(&test.token_admin).incr_allow(
+        &Signature::Invoker,
+        &0,
+        &Identifier::Contract(rsvp.contract_id.clone()),
+        &300,
+    );
+    admin.sponsor(&admin_id, &300);
+
+    let users = [
+        test.account_id_to_identifier(&test.attendee_users[0]),
+        test.account_id_to_identifier(&test.attendee_users[1]),
+        test.account_id_to_identifier(&test.attendee_users[2]),
+    ];
+    for user in users.iter() {
+        rsvp.deposit(user, &None);
+    }
+    admin.attend(&users[0], &None);
+    admin.attend(&users[1], &None);
+
+    admin.withdraw(&2, &0);
+    assert_eq!(test.token.balance(&users[0]), 1150);
+    assert_eq!(test.token.balance(&users[1]), 1150);
+    assert_eq!(test.token.balance(&users[2]), 1000);
+}
+
+#[test]
+fn test_withdraw_skips_failed_transfer() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+    let token_admin = test.token.with_source_account(&test.token_admin);
+    token_admin.freeze(&Signature::Invoker, &0, &users[0]);
+
+    let result = test.call_withdraw(3, 0);
+    assert_eq!(result.paid, 2);
+    assert_eq!(result.skipped, 1);
+    assert!(test.contract.attendee(&users[0]).payout_failed());
+
+    token_admin.unfreeze(&Signature::Invoker, &0, &users[0]);
+    test.contract.with_source_account(&test.token_admin).payout_one(&users[0]);
+    let att = test.contract.attendee(&users[0]);
+    assert!(att.refunded() && !att.payout_failed());
+    assert_eq!(test.token.balance(&users[0]), 1066);
+}
+
+#[test]
+fn test_withdraw_skips_failed_split_transfer() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let employer = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.approve_deposit(50, test.attendee_users[0].clone());
+    test.approve_deposit(150, test.attendee_users[1].clone());
+    test.contract
+        .with_source_account(&test.attendee_users[1])
+        .dep_split(&user0, &employer, &7_500);
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user2);
+    test.attend(&user0);
+
+    // The employer's 300 goes through, the attendee's 100 is refused
+    let token_admin = test.token.with_source_account(&test.token_admin);
+    token_admin.freeze(&Signature::Invoker, &0, &user0);
+    let result = test.call_withdraw(1, 0);
+    assert_eq!(result.paid, 0);
+    assert_eq!(result.skipped, 1);
+    assert_eq!(test.token.balance(&employer), 1150);
+    let att = test.contract.attendee(&user0);
+    assert!(att.payout_failed());
+    assert_eq!(att.claimed, 300);
+
+    // Retrying sends only the attendee's part
+    token_admin.unfreeze(&Signature::Invoker, &0, &user0);
+    test.contract.with_source_account(&test.token_admin).payout_one(&user0);
+    assert_eq!(test.token.balance(&user0), 1050);
+    assert_eq!(test.token.balance(&employer), 1150);
+    assert!(test.contract.attendee(&user0).refunded());
+}
+
+#[test]
+#[should_panic(expected = "price out of bounds")]
+fn test_tier_price_out_of_bounds() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+
+    let action_id = admin.propose(&AdminAction::SetBounds(100, 1000));
+    assert!(test.contract.proposal(&action_id).executed);
+    assert_eq!(test.contract.bounds(), Some((100, 1000)));
+
+    admin.set_tier(&1, &200_000, &10);
+}
+
+#[cfg(feature = "journal")]
+#[test]
+fn test_journal_records_writes() {
+    use soroban_sdk::xdr::ScVal;
+
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+
+    journal::clear();
+    test.deposit(&user);
+
+    let registered = journal::key_of(env, DataKey::Registered);
+    let entries = journal::entries();
+    let write = entries.iter().find(|entry| entry.key == registered).unwrap();
+    assert_eq!(write.old, Some(ScVal::U32(0)));
+    assert_eq!(write.new, Some(ScVal::U32(1)));
+}
+
+#[test]
+fn test_receipt_token() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    let receipt_id = env.register_contract_wasm(None, WASM);
+    let receipt = TokenClient::new(env, &receipt_id);
+    receipt.initialize(
+        &Identifier::Contract(test.contract.contract_id.clone()),
+        &0u32,
+        &"receipt".into_val(env),
+        &"RCPT".into_val(env),
+    );
+    test.contract.with_source_account(&test.token_admin).set_rcpt(&receipt_id);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user);
+    assert_eq!(receipt.balance(&user), 1);
+
+    test.attend(&user);
+    assert_eq!(receipt.balance(&user), 0);
+}
+
+#[test]
+fn test_remaining_unchecked() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    assert_eq!(test.contract.remaining(), 2);
+
+    test.attend(&user0);
+    assert_eq!(test.contract.remaining(), 1);
+    test.attend(&user1);
+    assert_eq!(test.contract.remaining(), 0);
+}
+
+#[test]
+fn test_scheduled_phases() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    admin.set_sched(&Schedule {
+        reg_end: 13000,
+        event_at: 14000,
+        claim_open: 15000,
+        claim_end: 16000,
+    });
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user);
+    assert_eq!(test.contract.phase(), Phase::Register);
+
+    test.set_timestamp(14000);
+    assert_eq!(test.contract.phase(), Phase::CheckIn);
+    test.attend(&user);
+
+    test.set_timestamp(15000);
+    assert_eq!(test.contract.phase(), Phase::Paying);
+    assert_eq!(test.withdraw(1, 0), 1);
+    assert_eq!(test.contract.phase(), Phase::Settled);
+}
+
+#[test]
+#[should_panic(expected = "registration closed")]
+fn test_deposit_after_registration_close() {
+    let test = DistributionTest::setup();
+    test.contract.with_source_account(&test.token_admin).set_sched(&Schedule {
+        reg_end: 13000,
+        event_at: 14000,
+        claim_open: 15000,
+        claim_end: 16000,
+    });
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.set_timestamp(13000);
+    test.deposit(&user);
+}
+
+#[test]
+fn test_deposit_with_contact_hash() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+
+    let contact_hash = env.crypto().sha256(&Bytes::from_slice(env, b"alice@example.com"));
+    test.contract.deposit(&user, &Some(contact_hash.clone()));
+    assert_eq!(test.contract.attendee(&user).contact, Some(contact_hash));
+}
+
+#[test]
+#[should_panic(expected = "transfer must be signed")]
+fn test_deposit_with_transfer_needs_signature() {
+    let test = DistributionTest::setup();
+    let user = test.account_id_to_identifier(&test.attendee_users[0]);
+    test.contract
+        .with_source_account(&test.attendee_users[0])
+        .dep_xfer(&user, &Signature::Invoker, &0);
+}
+
+#[test]
+fn test_whole_unit_prices() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let mut config = InitConfig::new(
+        Identifier::Account(test.token_admin.clone()),
+        2,
+        test.token.contract_id.clone(),
+    );
+    config.whole = true;
+    let contract = DistributionContractClient::new(env, env.register_contract(None, DistributionContract {}));
+    contract.initialize(&config);
+
+    assert_eq!(contract.decimals(), 7);
+    assert_eq!(contract.stats().price, 20_000_000);
+    contract.with_source_account(&test.token_admin).set_tier(&1, &3, &10);
+    assert_eq!(contract.get_tier(&1).price, 30_000_000);
+}
+
+#[test]
+fn test_late_check_in_refunds_deposit_only() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    admin.set_sched(&Schedule {
+        reg_end: 13000,
+        event_at: 14000,
+        claim_open: 15000,
+        claim_end: 20000,
+    });
+    admin.set_grace(&600);
+    let users = [
+        test.account_id_to_identifier(&test.attendee_users[0]),
+        test.account_id_to_identifier(&test.attendee_users[1]),
+        test.account_id_to_identifier(&test.attendee_users[2]),
+    ];
+    for (user, account) in users.iter().zip(test.attendee_users.iter()) {
+        test.approve_deposit(200, account.clone());
+        test.deposit(user);
+    }
+
+    test.set_timestamp(14000);
+    test.attend(&users[0]);
+    test.set_timestamp(15300);
+    assert_eq!(admin.attend(&users[1], &None), NO_WITHDRAWAL_INDEX);
+    assert_eq!(test.token.balance(&users[1]), 1000);
+    assert!(test.contract.attendee(&users[1]).late());
+
+    test.set_timestamp(15600);
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&users[0]), 1200);
+    assert_eq!(test.contract.stats().attended, 2);
+    // Late arrivals commit their returned fee without a withdrawal slot
+    assert!(test.contract.commit(&10).is_some());
+}
+
+#[test]
+fn test_distribution_by_time() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+
+    test.set_timestamp(1000);
+    test.attend(&user0);
+    test.attend(&user1);
+    test.set_timestamp(2000);
+    admin.check_out(&user1);
+    test.set_timestamp(4000);
+    admin.check_out(&user0);
+
+    // user0 was present three times as long as user1
+    admin.finalize(&DistributionMode::ByTime);
+    test.withdraw(2, 0);
+    assert_eq!(test.token.balance(&user0), 1150);
+    assert_eq!(test.token.balance(&user1), 1050);
+    assert_eq!(test.token.balance(&user2), 800);
+}
+
+#[test]
+#[should_panic(expected = "already checked out")]
+fn test_check_out_twice() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    test.attend(&user0);
+    admin.check_out(&user0);
+    admin.check_out(&user0);
+}
+
+#[test]
+fn test_withdrawal_index_mapping() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    assert_eq!(test.contract.idx_range(), (0, 0));
+
+    test.attend(&user2);
+    test.attend(&user0);
+    assert_eq!(test.contract.idx_range(), (0, 2));
+    assert_eq!(test.contract.id_at(&0), Some(user2));
+    assert_eq!(test.contract.id_at(&1), Some(user0));
+    assert_eq!(test.contract.id_at(&2), None);
+
+    let (low, high) = test.contract.idx_range();
+    assert_eq!(test.withdraw(high, low), 2);
+}
+
+#[test]
+#[should_panic(expected = "no attendees")]
+fn test_withdraw_without_attendees() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    test.withdraw(5, 0);
+}
+
+#[test]
+fn test_payout_split_into_principal_and_bonus() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.deposit(&user2);
+    test.attend(&user0);
+    test.attend(&user1);
+
+    test.withdraw(2, 0);
+    let att = test.contract.attendee(&user0);
+    assert_eq!(att.princ_paid, 200);
+    assert_eq!(att.bonus_paid, 100);
+}
+
+#[test]
+fn test_deposit_sub() {
+    let test = DistributionTest::setup();
+    let exchange = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let alice = test.contract.sub_ident(&exchange, &1);
+    let bob = test.contract.sub_ident(&exchange, &2);
+    assert_ne!(alice, bob);
+
+    test.approve_deposit(400, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    let shared = test.contract.with_source_account(&test.attendee_users[0]);
+    shared.dep_sub(&exchange, &1);
+    shared.dep_sub(&exchange, &2);
+    test.deposit(&user1);
+    assert_eq!(test.token.balance(&exchange), 600);
+    assert_eq!(test.contract.sub_acct(&alice), Some(SubAccount { owner: exchange.clone(), sub_id: 1 }));
+
+    // Only one sub-id shows up; the payout goes to the shared account
+    test.attend(&alice);
+    test.withdraw(1, 0);
+    assert_eq!(test.token.balance(&exchange), 1200);
+}
+
+#[test]
+#[should_panic(expected = "attendee already registered")]
+fn test_deposit_sub_twice() {
+    let test = DistributionTest::setup();
+    let exchange = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    test.approve_deposit(400, test.attendee_users[0].clone());
+    let shared = test.contract.with_source_account(&test.attendee_users[0]);
+    shared.dep_sub(&exchange, &1);
+    shared.dep_sub(&exchange, &1);
+}
+
+#[test]
+#[should_panic(expected = "attendee not attested")]
+fn test_deposit_not_attested() {
+    let test = DistributionTest::setup();
+    let attestor_id = test.contract.env.register_contract(None, MockAttestor {});
+    test.contract.with_source_account(&test.token_admin).set_attest(&attestor_id);
+
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+    assert_eq!(test.contract.stats().registered, 1);
+
+    let bytes = BytesN::from_array(&test.contract.env, &[7; 32]);
+    test.contract.with_source_account(&test.token_admin).comp(&Identifier::Contract(bytes));
+}
+
+#[test]
+fn test_withdraw_with_refusing_token() {
+    let test = DistributionTest::setup_with_token(create_mock_token);
+    let users = setup_with_remainder(&test);
+    let mock = MockTokenClient::new(&test.contract.env, &test.token.contract_id);
+    mock.fail_for(&users[1], &true);
+    mock.fail_after(&1);
+
+    let result = test.call_withdraw(3, 0);
+    assert_eq!(result.paid, 1);
+    assert_eq!(result.skipped, 2);
+    assert_eq!(test.token.balance(&users[0]), 1066);
+    assert!(test.contract.attendee(&users[1]).payout_failed());
+    assert!(test.contract.attendee(&users[2]).payout_failed());
+
+    mock.fail_for(&users[1], &false);
+    mock.fail_after(&2);
+    test.contract.with_source_account(&test.token_admin).payout_one(&users[1]);
+    test.contract.with_source_account(&test.token_admin).payout_one(&users[2]);
+    assert_eq!(test.token.balance(&users[1]), 1066);
+    assert_eq!(test.token.balance(&users[2]), 1066);
+}
+
+#[test]
+fn test_parent_event_bundle_discount() {
+    let test = DistributionTest::setup();
+    let env = &test.contract.env;
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    let workshop = create_distribution_contract(env, &test.token_admin, test.token.contract_id.clone());
+    workshop.with_source_account(&test.token_admin).set_parent(&test.contract.contract_id, &2500);
+
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.deposit(&user0);
+
+    // Registered for the conference, so the workshop is 25% off
+    for user in [&test.attendee_users[0], &test.attendee_users[1]] {
+        test.token.with_source_account(user).incr_allow(
+            &Signature::Invoker,
+            &0,
+            &Identifier::Contract(workshop.contract_id.clone()),
+            &200,
+        );
+    }
+    assert_eq!(workshop.deposit(&user0, &None), (0, 150));
+    assert_eq!(workshop.deposit(&user1, &None), (1, 200));
+}
+
+#[test]
+fn test_erase_me() {
+    let test = DistributionTest::setup();
+    let users = setup_with_remainder(&test);
+    test.withdraw(3, 0);
+
+    test.contract.with_source_account(&test.attendee_users[0]).erase_me(&users[0]);
+    assert!(!test.contract.is_reg(&users[0]));
+    assert_eq!(
+        test.contract.tombstone(&0),
+        Some(Tombstone { fee: 200, flags: FLAG_ATTENDED | FLAG_REFUNDED, paid: 266 })
+    );
+    assert_ne!(test.contract.id_at(&0), Some(users[0].clone()));
+    assert_eq!(test.contract.stats().purged, 1);
+    assert_eq!(test.withdraw(3, 0), 0);
+}
+
+#[test]
+fn test_price_steps() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    admin.set_steps(&vec![&test.contract.env, (20000, 100), (30000, 150)]);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+
+    assert_eq!(test.contract.next_price(), Some((20000, 150)));
+    assert_eq!(test.contract.deposit(&user0, &None), (0, 100));
+
+    test.set_timestamp(25000);
+    assert_eq!(test.contract.next_price(), Some((30000, 200)));
+    assert_eq!(test.contract.quote(&user1, &None), 150);
+    assert_eq!(test.contract.deposit(&user1, &None), (1, 150));
+
+    // Past the last step the base price applies
+    test.set_timestamp(30000);
+    assert_eq!(test.contract.next_price(), None);
+    assert_eq!(test.contract.deposit(&user2, &None), (2, 200));
+}
+
+#[test]
+fn test_min_registrations_missed() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+
+    test.contract.with_source_account(&test.token_admin).set_minreg(&3, &20000);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.deposit(&user0);
+    test.deposit(&user1);
+    test.attend(&user0);
+
+    // Any registrant can trigger the refunds
+    test.set_timestamp(20000);
+    test.contract.with_source_account(&test.attendee_users[1]).refund(&10, &0);
+    assert_eq!(test.token.balance(&user0), 1000);
+    assert_eq!(test.token.balance(&user1), 1000);
+}
+
+#[test]
+fn test_referral_bonus() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+    let user1 = test.account_id_to_identifier(&test.attendee_users[1]);
+    let user2 = test.account_id_to_identifier(&test.attendee_users[2]);
+
+    test.contract.with_source_account(&test.token_admin).set_ref(&20);
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.approve_deposit(200, test.attendee_users[1].clone());
+    test.approve_deposit(200, test.attendee_users[2].clone());
+    test.deposit(&user0);
+    test.contract.dep_ref(&user1, &user0);
+    test.contract.dep_ref(&user2, &user0);
+
+    // Only the referral who showed up counts
+    test.attend(&user0);
+    test.attend(&user1);
+    test.withdraw(2, 0);
+    assert_eq!(test.token.balance(&user1), 1090);
+
+    assert_eq!(test.contract.with_source_account(&test.attendee_users[0]).claim_refs(&user0), 20);
+    assert_eq!(test.token.balance(&user0), 1110);
+}
+
+#[test]
+fn test_dry_run_checks() {
+    let test = DistributionTest::setup();
+    let user0 = test.account_id_to_identifier(&test.attendee_users[0]);
+
+    assert_eq!(test.contract.try_can_dep(&user0, &None), Err(Ok(crate::Error::AllowanceTooLow)));
+    assert_eq!(test.contract.try_can_dep(&user0, &Some(7)), Err(Ok(crate::Error::NoTier)));
+    test.approve_deposit(200, test.attendee_users[0].clone());
+    test.contract.can_dep(&user0, &None);
+    assert_eq!(test.contract.try_can_attend(&user0), Err(Ok(crate::Error::NotRegistered)));
+
+    // Nothing was registered by the checks
+    assert_eq!(test.contract.stats().registered, 0);
+    test.deposit(&user0);
+    test.contract.can_attend(&user0);
+    assert_eq!(test.contract.try_can_dep(&user0, &None), Err(Ok(crate::Error::AlreadyRegistered)));
+
+    test.attend(&user0);
+    assert_eq!(test.contract.try_can_attend(&user0), Err(Ok(crate::Error::AlreadyAttended)));
+}
+
+#[test]
+#[should_panic(expected = "seat limit reached")]
+fn test_seat_limit_for_captain() {
+    let test = DistributionTest::setup();
+    let captain = test.account_id_to_identifier(&test.attendee_users[0]);
+    let members = vec![
+        &test.contract.env,
+        test.account_id_to_identifier(&test.attendee_users[1]),
+        test.account_id_to_identifier(&test.attendee_users[2]),
+        test.account_id_to_identifier(&test.new_user()),
+    ];
+
+    test.contract.with_source_account(&test.token_admin).set_seats(&2);
+    test.approve_deposit(600, test.attendee_users[0].clone());
+    test.contract
+        .with_source_account(&test.attendee_users[0])
+        .reg_team(&captain, &members, &symbol!("whales"));
+}
+
+#[test]
+fn test_frozen_payout_reserved_from_sweep() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let beneficiary = test.account_id_to_identifier(&test.token_admin);
+    admin.set_sched(&Schedule {
+        reg_end: 13000,
+        event_at: 14000,
+        claim_open: 15000,
+        claim_end: 16000,
+    });
+    admin.set_remain(&RemainderPolicy::Sweep);
+    admin.set_benef(&beneficiary);
+    let users = [
+        test.account_id_to_identifier(&test.attendee_users[0]),
+        test.account_id_to_identifier(&test.attendee_users[1]),
+        test.account_id_to_identifier(&test.attendee_users[2]),
+    ];
+    for (user, account) in users.iter().zip(test.attendee_users.iter()) {
+        test.approve_deposit(200, account.clone());
+        test.deposit(user);
+    }
+
+    test.set_timestamp(14000);
+    test.attend(&users[0]);
+    test.attend(&users[1]);
+    admin.freeze(&users[1]);
+    test.set_timestamp(15000);
+    assert_eq!(test.withdraw(2, 0), 1);
+
+    // The frozen attendee's 300 is held back from the sweep and still payable once cleared
+    test.set_timestamp(16000);
+    assert_eq!(admin.sweep_rem(), 0);
+    admin.unfreeze(&users[1]);
+    assert_eq!(admin.payout_one(&users[1]), 300);
+    assert_eq!(test.token.balance(&users[1]), 1100);
+    assert_eq!(test.token.balance(&beneficiary), 1000);
+}
+
+#[test]
+fn test_release_reserve_timelocked() {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    let beneficiary = test.account_id_to_identifier(&test.token_admin);
+    admin.set_sched(&Schedule {
+        reg_end: 13000,
+        event_at: 14000,
+        claim_open: 15000,
+        claim_end: 16000,
+    });
+    admin.set_remain(&RemainderPolicy::Sweep);
+    admin.set_benef(&beneficiary);
+    let users = [
+        test.account_id_to_identifier(&test.attendee_users[0]),
+        test.account_id_to_identifier(&test.attendee_users[1]),
+    ];
+    for (user, account) in users.iter().zip(test.attendee_users.iter()) {
+        test.approve_deposit(200, account.clone());
+        test.deposit(user);
+    }
+
+    test.set_timestamp(14000);
+    test.attend(&users[0]);
+    test.attend(&users[1]);
+    admin.freeze(&users[1]);
+    test.set_timestamp(15000);
+    assert_eq!(test.withdraw(2, 0), 1);
+    test.set_timestamp(16000);
+    admin.sweep_rem();
+
+    // The release waits out the timelock like a sweep
+    admin.set_tlock(&3600);
+    assert!(admin.try_release(&users[1]).is_err());
+    let action_id = admin.propose(&AdminAction::Release(users[1].clone()));
+    test.set_timestamp(16000 + 3600);
+    admin.execute(&action_id);
+    assert!(test.contract.proposal(&action_id).executed);
+    assert_eq!(test.token.balance(&beneficiary), 1200);
+}
@@ -0,0 +1,83 @@
+// Instruction budgets per entrypoint, measured with the host budget tracker.
+// Run with `cargo test --features bench -- --nocapture` to see the numbers.
+
+extern crate std;
+
+use super::*;
+use std::println;
+use std::vec::Vec as StdVec;
+
+// Per-invocation CPU instruction limit of the network
+const CPU_LIMIT: u64 = 40_000_000;
+// Most a call may cost with `SCALE` times as many attendees, in percent of the smaller run.
+// Batch entrypoints must not get more expensive with the size of the event.
+const MAX_GROWTH_PCT: u64 = 150;
+const SCALE: u32 = 3;
+
+struct Costs {
+    deposit: u64,
+    attend: u64,
+    simulate: u64,
+    withdraw: u64,
+}
+
+fn measure<F: FnOnce()>(env: &Env, call: F) -> u64 {
+    env.budget().reset();
+    call();
+    env.budget().cpu_instruction_cost()
+}
+
+// Costs of the last registration, the last check-in and a full batch once `attendees` are in
+fn run(attendees: u32) -> Costs {
+    let test = DistributionTest::setup();
+    let env = test.contract.env.clone();
+    let admin = test.contract.with_source_account(&test.token_admin);
+
+    let mut ids = StdVec::new();
+    for _ in 0..attendees {
+        let user = test.new_user();
+        test.approve_deposit(200, user.clone());
+        ids.push(test.account_id_to_identifier(&user));
+    }
+
+    let (last, rest) = ids.split_last().unwrap();
+    for id in rest.iter() {
+        test.deposit(id);
+        test.attend(id);
+    }
+    let deposit = measure(&env, || test.deposit(last));
+    let attend = measure(&env, || test.attend(last));
+    let simulate = measure(&env, || {
+        test.contract.simulate(&MAX_BATCH, &0);
+    });
+    admin.finalize(&DistributionMode::InKind);
+    let withdraw = measure(&env, || {
+        test.call_withdraw(MAX_BATCH, 0);
+    });
+
+    println!(
+        "{} attendees: deposit {}, attend {}, simulate {}, withdraw {}",
+        attendees, deposit, attend, simulate, withdraw
+    );
+    Costs { deposit, attend, simulate, withdraw }
+}
+
+fn check(name: &str, small: u64, large: u64) {
+    assert!(large <= CPU_LIMIT, "{} exceeds the CPU limit: {}", name, large);
+    assert!(
+        large * 100 <= small * MAX_GROWTH_PCT,
+        "{} grows with the number of attendees: {} -> {}",
+        name, small, large
+    );
+}
+
+#[test]
+fn bench_entrypoints() {
+    let small = run(MAX_BATCH);
+    let large = run(MAX_BATCH * SCALE);
+
+    check("deposit", small.deposit, large.deposit);
+    check("attend", small.attend, large.attend);
+    check("simulate_distribution", small.simulate, large.simulate);
+    check("withdraw", small.withdraw, large.withdraw);
+}
@@ -0,0 +1,224 @@
+// State-machine fuzzer: random call sequences are run against both the contract and a
+// plain model of it. On a mismatch the sequence is shrunk before reporting.
+
+extern crate std;
+
+use super::*;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::vec::Vec as StdVec;
+
+const USERS: usize = 6;
+const RUNS: usize = 20;
+const OPS: usize = 16;
+const TIER_PRICE: i128 = 100;
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Deposit(usize),
+    DepositTier(usize),
+    Comp(usize),
+    Attend(usize),
+    Withdraw(u32, u32),
+}
+
+#[derive(Clone, Copy, Default)]
+struct ModelAttendee {
+    registered: bool,
+    fee: i128,
+    comped: bool,
+    attended: bool,
+    refunded: bool,
+}
+
+struct Model {
+    attendees: [ModelAttendee; USERS],
+    balances: [i128; USERS],
+    // Users in withdrawal index order
+    withdrawals: StdVec<usize>,
+    comp_attended: u32,
+    unclaimed: i128,
+    finalized: bool,
+}
+
+impl Model {
+    fn new() -> Self {
+        Model {
+            attendees: [ModelAttendee::default(); USERS],
+            balances: [1000; USERS],
+            withdrawals: StdVec::new(),
+            comp_attended: 0,
+            unclaimed: 0,
+            finalized: false,
+        }
+    }
+
+    fn register(&mut self, user: usize, fee: i128, comped: bool) {
+        self.attendees[user] = ModelAttendee { registered: true, fee, comped, ..Default::default() };
+        self.balances[user] -= fee;
+        self.unclaimed += fee;
+    }
+
+    // Applies `op` and returns whether the contract should accept it
+    fn apply(&mut self, op: Op) -> bool {
+        match op {
+            Op::Deposit(user) | Op::DepositTier(user) | Op::Comp(user) => {
+                if self.finalized || self.attendees[user].registered {
+                    return false;
+                }
+                match op {
+                    Op::Deposit(_) => self.register(user, 200, false),
+                    Op::DepositTier(_) => self.register(user, TIER_PRICE, false),
+                    _ => self.register(user, 0, true),
+                }
+            }
+            Op::Attend(user) => {
+                let att = &mut self.attendees[user];
+                if self.finalized || !att.registered || att.attended {
+                    return false;
+                }
+                att.attended = true;
+                if att.comped {
+                    self.comp_attended += 1;
+                } else {
+                    self.unclaimed -= att.fee;
+                    self.withdrawals.push(user);
+                }
+            }
+            Op::Withdraw(high, low) => {
+                if high < low || high - low > 10 {
+                    return false;
+                }
+                // Distribution can't start before anyone attended
+                if !self.finalized && self.withdrawals.is_empty() && self.comp_attended == 0 {
+                    return false;
+                }
+                self.finalized = true;
+                let count = self.withdrawals.len();
+                let mut share = 0;
+                if count > 0 {
+                    share = self.unclaimed / count as i128;
+                }
+                for id in low as usize..(high as usize).min(count) {
+                    let user = self.withdrawals[id];
+                    if !self.attendees[user].refunded {
+                        self.attendees[user].refunded = true;
+                        self.balances[user] += self.attendees[user].fee + share;
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+// Deterministic xorshift, so failures reproduce
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+fn random_op(rng: &mut Rng) -> Op {
+    let user = rng.below(USERS as u64) as usize;
+    match rng.below(5) {
+        0 => Op::Deposit(user),
+        1 => Op::DepositTier(user),
+        2 => Op::Comp(user),
+        3 => Op::Attend(user),
+        _ => {
+            let low = rng.below(USERS as u64) as u32;
+            Op::Withdraw(low + rng.below(11) as u32, low)
+        }
+    }
+}
+
+// Runs `ops` on a fresh contract, skipping the ones the model rejects, and
+// compares balances and stats after every step
+fn run(ops: &[Op]) {
+    let test = DistributionTest::setup();
+    let admin = test.contract.with_source_account(&test.token_admin);
+    admin.set_tier(&1, &TIER_PRICE, &(USERS as u32));
+
+    let mut accounts = StdVec::new();
+    for user in test.attendee_users.iter() {
+        accounts.push(user.clone());
+    }
+    while accounts.len() < USERS {
+        accounts.push(test.new_user());
+    }
+    for account in accounts.iter() {
+        test.approve_deposit(1000, account.clone());
+    }
+    let ids: StdVec<Identifier> = accounts.iter().map(|a| test.account_id_to_identifier(a)).collect();
+
+    let mut model = Model::new();
+    for op in ops.iter() {
+        if !model.apply(*op) {
+            continue;
+        }
+        match *op {
+            Op::Deposit(user) => {
+                test.deposit(&ids[user]);
+            }
+            Op::DepositTier(user) => {
+                test.contract.dep_tier(&ids[user], &1);
+            }
+            Op::Comp(user) => admin.comp(&ids[user]),
+            Op::Attend(user) => {
+                admin.attend(&ids[user], &None);
+            }
+            Op::Withdraw(high, low) => {
+                test.call_withdraw(high, low);
+            }
+        }
+
+        for user in 0..USERS {
+            assert_eq!(test.token.balance(&ids[user]), model.balances[user], "balance of user {}", user);
+        }
+        let stats = test.contract.stats();
+        let registered = model.attendees.iter().filter(|a| a.registered).count() as u32;
+        assert_eq!(stats.registered, registered);
+        assert_eq!(stats.attended, model.withdrawals.len() as u32 + model.comp_attended);
+        assert_eq!(stats.unclaimed, model.unclaimed);
+    }
+}
+
+fn fails(ops: &[Op]) -> bool {
+    catch_unwind(AssertUnwindSafe(|| run(ops))).is_err()
+}
+
+// Drop single calls for as long as the sequence keeps failing
+fn shrink(mut ops: StdVec<Op>) -> StdVec<Op> {
+    let mut i = 0;
+    while i < ops.len() {
+        let mut candidate = ops.clone();
+        candidate.remove(i);
+        if fails(&candidate) {
+            ops = candidate;
+            i = 0;
+        } else {
+            i += 1;
+        }
+    }
+    ops
+}
+
+#[test]
+fn fuzz_state_transitions() {
+    let mut rng = Rng(0x5eed_1234_abcd_ef01);
+    for _ in 0..RUNS {
+        let ops: StdVec<Op> = (0..OPS).map(|_| random_op(&mut rng)).collect();
+        if fails(&ops) {
+            panic!("contract diverged from the model, minimal sequence: {:?}", shrink(ops));
+        }
+    }
+}
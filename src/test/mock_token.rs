@@ -0,0 +1,143 @@
+// Minimal token with the same interface as the token contract, which can be told to refuse
+// transfers to or from given identifiers, or every transfer after the next few.
+// Signatures are not checked: the invoker or the signing key is taken as the spender.
+
+use super::*;
+
+#[derive(Clone)]
+#[contracttype]
+enum MockKey {
+    Admin,
+    Balance(Identifier),
+    Allowance(Identifier, Identifier),
+    Failing(Identifier),
+    // Transfers left before every further one fails
+    FailAfter,
+}
+
+pub struct MockToken;
+
+fn signer(env: &Env, sig: &Signature) -> Identifier {
+    match sig {
+        Signature::Invoker => env.invoker().into(),
+        Signature::Ed25519(sig) => Identifier::Ed25519(sig.public_key.clone()),
+        Signature::Account(sigs) => Identifier::Account(sigs.account_id.clone()),
+    }
+}
+
+fn read_balance(env: &Env, id: &Identifier) -> i128 {
+    env.storage().get(MockKey::Balance(id.clone())).unwrap_or(Ok(0)).unwrap()
+}
+
+fn read_allowance(env: &Env, from: &Identifier, spender: &Identifier) -> i128 {
+    env.storage()
+        .get(MockKey::Allowance(from.clone(), spender.clone()))
+        .unwrap_or(Ok(0))
+        .unwrap()
+}
+
+fn check_admin(env: &Env, sig: &Signature) {
+    let admin: Identifier = env.storage().get_unchecked(MockKey::Admin).unwrap();
+    if signer(env, sig) != admin {
+        panic!("not authorized by admin");
+    }
+}
+
+fn move_balance(env: &Env, from: &Identifier, to: &Identifier, amount: i128) {
+    if env.storage().has(MockKey::Failing(from.clone())) || env.storage().has(MockKey::Failing(to.clone())) {
+        panic!("transfer refused");
+    }
+    if env.storage().has(MockKey::FailAfter) {
+        let left: u32 = env.storage().get_unchecked(MockKey::FailAfter).unwrap();
+        if left == 0 {
+            panic!("transfer refused");
+        }
+        env.storage().set(MockKey::FailAfter, left - 1);
+    }
+
+    let balance = read_balance(env, from);
+    if balance < amount {
+        panic!("insufficient balance");
+    }
+    env.storage().set(MockKey::Balance(from.clone()), balance - amount);
+    env.storage().set(MockKey::Balance(to.clone()), read_balance(env, to) + amount);
+}
+
+#[contractimpl]
+impl MockToken {
+    pub fn initialize(env: Env, admin: Identifier, _decimal: u32, _name: Bytes, _symbol: Bytes) {
+        env.storage().set(MockKey::Admin, admin);
+    }
+
+    pub fn decimals(_env: Env) -> u32 {
+        7
+    }
+
+    pub fn balance(env: Env, id: Identifier) -> i128 {
+        read_balance(&env, &id)
+    }
+
+    pub fn allowance(env: Env, from: Identifier, spender: Identifier) -> i128 {
+        read_allowance(&env, &from, &spender)
+    }
+
+    pub fn incr_allow(env: Env, from: Signature, _nonce: i128, spender: Identifier, amount: i128) {
+        let from = signer(&env, &from);
+        let allowance = read_allowance(&env, &from, &spender);
+        env.storage().set(MockKey::Allowance(from, spender), allowance + amount);
+    }
+
+    pub fn xfer(env: Env, from: Signature, _nonce: i128, to: Identifier, amount: i128) {
+        move_balance(&env, &signer(&env, &from), &to, amount);
+    }
+
+    pub fn xfer_from(env: Env, spender: Signature, _nonce: i128, from: Identifier, to: Identifier, amount: i128) {
+        let spender = signer(&env, &spender);
+        let allowance = read_allowance(&env, &from, &spender);
+        if allowance < amount {
+            panic!("insufficient allowance");
+        }
+        env.storage().set(MockKey::Allowance(from.clone(), spender), allowance - amount);
+        move_balance(&env, &from, &to, amount);
+    }
+
+    pub fn mint(env: Env, admin: Signature, _nonce: i128, to: Identifier, amount: i128) {
+        check_admin(&env, &admin);
+        env.storage().set(MockKey::Balance(to.clone()), read_balance(&env, &to) + amount);
+    }
+
+    pub fn burn(env: Env, admin: Signature, _nonce: i128, from: Identifier, amount: i128) {
+        check_admin(&env, &admin);
+        let balance = read_balance(&env, &from);
+        if balance < amount {
+            panic!("insufficient balance");
+        }
+        env.storage().set(MockKey::Balance(from), balance - amount);
+    }
+
+    // Refuse every transfer to or from `id` while `failing` is set
+    pub fn fail_for(env: Env, id: Identifier, failing: bool) {
+        if failing {
+            env.storage().set(MockKey::Failing(id), ());
+        } else {
+            env.storage().remove(MockKey::Failing(id));
+        }
+    }
+
+    // Let the next `transfers` transfers through and refuse every one after
+    pub fn fail_after(env: Env, transfers: u32) {
+        env.storage().set(MockKey::FailAfter, transfers);
+    }
+}
+
+pub fn create_mock_token(e: &Env, admin: &AccountId) -> (BytesN<32>, TokenClient) {
+    let id = e.register_contract(None, MockToken {});
+    let token = TokenClient::new(e, &id);
+    token.initialize(
+        &Identifier::Account(admin.clone()),
+        &7u32,
+        &"name".into_val(e),
+        &"symbol".into_val(e),
+    );
+    (id, token)
+}